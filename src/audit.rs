@@ -0,0 +1,121 @@
+use std::io::Write;
+use chrono::{DateTime, Utc};
+use twine_protocol::prelude::*;
+
+use crate::RngError;
+
+/// A record of a single randomness verification
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AuditEvent {
+  /// Whether the verification succeeded
+  pub passed: bool,
+  /// The index of the verified tixel on its strand
+  pub index: u64,
+  /// The CID of the verified tixel
+  pub cid: String,
+  /// The CID of the strand the tixel belongs to
+  pub strand_cid: String,
+  /// The extracted randomness as hex, if verification succeeded
+  pub output: Option<String>,
+  /// The reason verification failed, if it did
+  pub error: Option<String>,
+  /// When the verification was performed
+  pub verified_at: DateTime<Utc>,
+}
+
+/// A destination for audit events
+pub trait AuditSink {
+  fn record(&mut self, event: &AuditEvent) -> std::io::Result<()>;
+}
+
+/// An audit sink that writes each event as a line of JSON
+///
+/// Events are only ever appended, and the writer is flushed after
+/// every event so a crash doesn't lose verifications already relied upon.
+pub struct JsonAuditSink<W: Write> {
+  writer: W,
+}
+
+impl<W: Write> JsonAuditSink<W> {
+  pub fn new(writer: W) -> Self {
+    Self { writer }
+  }
+
+  pub fn into_inner(self) -> W {
+    self.writer
+  }
+}
+
+impl<W: Write> AuditSink for JsonAuditSink<W> {
+  fn record(&mut self, event: &AuditEvent) -> std::io::Result<()> {
+    serde_json::to_writer(&mut self.writer, event)?;
+    self.writer.write_all(b"\n")?;
+    self.writer.flush()
+  }
+}
+
+/// Extract the randomness from a twine pair, recording the outcome to an audit sink
+///
+/// An event is recorded whether or not verification succeeds. If the event
+/// can't be recorded, an error is returned even if the pulse was valid, so
+/// that no randomness is relied upon without a log entry.
+///
+/// [`crate::RandomnessBeacon`] and the randomness streams can record to a
+/// sink in the same way.
+pub fn extract_randomness_audited<S: AuditSink>(
+  current: &Twine,
+  prev: &Twine,
+  sink: &mut S,
+) -> Result<Vec<u8>, RngError> {
+  audited(sink, current, crate::extract_randomness(current, prev))
+}
+
+/// Record the outcome of verifying `current` to `sink`, passing it through
+pub(crate) fn audited<S: AuditSink + ?Sized>(
+  sink: &mut S,
+  current: &Twine,
+  result: Result<Vec<u8>, RngError>,
+) -> Result<Vec<u8>, RngError> {
+  let event = AuditEvent {
+    passed: result.is_ok(),
+    index: current.index(),
+    cid: current.cid().to_string(),
+    strand_cid: current.strand_cid().to_string(),
+    output: result.as_ref().ok().map(hex::encode),
+    error: result.as_ref().err().map(|e| e.to_string()),
+    verified_at: Utc::now(),
+  };
+  sink.record(&event)
+    .map_err(|e| VerificationError::General(format!("Failed to record audit event: {}", e)))?;
+  result
+}
+
+#[cfg(test)]
+mod test {
+  use crate::test_util::{builder, chain};
+  use crate::*;
+
+  #[test]
+  fn test_audit_events() {
+    let (builder, strand) = builder();
+    let tixels = chain(&builder, &strand, 2);
+    let (first, second) = (&tixels[0], &tixels[1]);
+
+    let mut sink = JsonAuditSink::new(Vec::new());
+    let rand = extract_randomness_audited(second, first, &mut sink).unwrap();
+    // wrong order
+    assert!(extract_randomness_audited(first, second, &mut sink).is_err());
+
+    let log = String::from_utf8(sink.into_inner()).unwrap();
+    let events: Vec<AuditEvent> = log.lines()
+      .map(|l| serde_json::from_str(l).unwrap())
+      .collect();
+    assert_eq!(events.len(), 2);
+    assert!(events[0].passed);
+    assert_eq!(events[0].index, 1);
+    assert_eq!(events[0].cid, second.cid().to_string());
+    assert_eq!(events[0].output, Some(hex::encode(rand)));
+    assert!(!events[1].passed);
+    assert!(events[1].error.is_some());
+  }
+}
@@ -0,0 +1,187 @@
+use std::sync::{Mutex, PoisonError};
+use chrono::{DateTime, Utc};
+use twine_protocol::prelude::*;
+use twine_protocol::twine_lib::Cid;
+
+use crate::audit::audited;
+use crate::resolution::{resolution_error, search_at_timestamp, MAX_ROUND_GAP};
+use crate::{extract_randomness_with_gap, AuditSink, resolve_round, RandomnessPayload, RngError, RngStrandDetails};
+
+/// A randomness beacon backed by a resolver
+///
+/// Every method resolves the requested pulse and its previous pulse, and
+/// validates them with [`extract_randomness_with_gap`], returning the pulse
+/// along with its verified randomness. Rounds may have been skipped between
+/// the two pulses, since pulses are looked up by round.
+///
+/// With [`RandomnessBeacon::with_audit_sink`], every verification is also
+/// recorded, as in [`crate::extract_randomness_audited`].
+///
+/// # Example
+///
+/// ```ignore
+/// let beacon = RandomnessBeacon::new(store, strand_cid);
+/// let (pulse, randomness) = beacon.latest().await?;
+/// ```
+pub struct RandomnessBeacon<R: Resolver> {
+  resolver: R,
+  strand_cid: Cid,
+  audit: Option<Mutex<Box<dyn AuditSink + Send>>>,
+}
+
+impl<R: Resolver> RandomnessBeacon<R> {
+  pub fn new(resolver: R, strand_cid: Cid) -> Self {
+    Self { resolver, strand_cid, audit: None }
+  }
+
+  /// Record every verification made by the beacon to `sink`
+  ///
+  /// If an event can't be recorded, the request fails even if the pulse
+  /// was valid.
+  pub fn with_audit_sink<S: AuditSink + Send + 'static>(mut self, sink: S) -> Self {
+    self.audit = Some(Mutex::new(Box::new(sink)));
+    self
+  }
+
+  pub fn resolver(&self) -> &R {
+    &self.resolver
+  }
+
+  pub fn strand_cid(&self) -> &Cid {
+    &self.strand_cid
+  }
+
+  async fn with_randomness(&self, tixel: Twine) -> Result<(Twine, Vec<u8>), RngError> {
+    let link = tixel.previous().ok_or(RngError::NoRandomness)?;
+    let prev = self.resolver.resolve(link).await.map_err(resolution_error)?.unpack();
+    let result = extract_randomness_with_gap(&tixel, &prev, MAX_ROUND_GAP);
+    let rand = match &self.audit {
+      Some(sink) => audited(&mut **sink.lock().unwrap_or_else(PoisonError::into_inner), &tixel, result)?,
+      None => result?,
+    };
+    Ok((tixel, rand))
+  }
+
+  /// The latest pulse and its randomness
+  pub async fn latest(&self) -> Result<(Twine, Vec<u8>), RngError> {
+    let latest = self.resolver.resolve_latest(&self.strand_cid).await.map_err(resolution_error)?.unpack();
+    self.with_randomness(latest).await
+  }
+
+  /// The pulse at a given tixel index and its randomness
+  ///
+  /// Tixel indices differ from rounds once pulses have been skipped; see
+  /// [`RandomnessBeacon::at_round`].
+  pub async fn at_index(&self, index: u64) -> Result<(Twine, Vec<u8>), RngError> {
+    let latest = self.resolver.resolve_latest(&self.strand_cid).await.map_err(resolution_error)?.unpack();
+    if index > latest.index() {
+      return Err(RngError::NotYetPublished);
+    }
+    let tixel = if index == latest.index() {
+      latest
+    } else {
+      self.resolver.resolve_index(&self.strand_cid, index).await.map_err(resolution_error)?.unpack()
+    };
+    self.with_randomness(tixel).await
+  }
+
+  /// The pulse for a given round (periods since genesis) and its randomness
+  ///
+  /// See [`resolve_round`].
+  pub async fn at_round(&self, round: u64) -> Result<(Twine, Vec<u8>), RngError> {
+    let tixel = resolve_round(&self.resolver, &self.strand_cid, round).await?;
+    self.with_randomness(tixel).await
+  }
+
+  /// The pulse covering a given instant and its randomness
+  ///
+  /// This is the latest pulse with a timestamp at or before `timestamp`.
+  /// If the next pulse after the latest published one would already cover
+  /// `timestamp`, the pulse isn't available yet.
+  pub async fn at(&self, timestamp: DateTime<Utc>) -> Result<(Twine, Vec<u8>), RngError> {
+    let latest = self.resolver.resolve_latest(&self.strand_cid).await.map_err(resolution_error)?.unpack();
+    let period = latest.strand().extract_details::<RngStrandDetails>()?.period;
+    let latest_timestamp = latest.extract_payload::<RandomnessPayload>()?.timestamp();
+    if timestamp >= latest_timestamp + period {
+      return Err(RngError::NotYetPublished);
+    }
+    let genesis = self.resolver.resolve_index(&self.strand_cid, 0).await.map_err(resolution_error)?.unpack();
+    let tixel = search_at_timestamp(&self.resolver, &self.strand_cid, timestamp, genesis, latest).await?;
+    self.with_randomness(tixel).await
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use futures::executor::block_on;
+  use chrono::TimeDelta;
+  use crate::test_util::{builder, chain, pulse_pair_at, store, SharedSink};
+  use crate::*;
+
+  #[test]
+  fn test_beacon() {
+    let (builder, strand) = builder();
+    let tixels = chain(&builder, &strand, 3);
+    let store = store(&strand, &tixels);
+    let ts = |t: &Twine| t.extract_payload::<RandomnessPayload>().unwrap().timestamp();
+
+    let beacon = RandomnessBeacon::new(store, strand.cid());
+
+    let (latest, rand) = block_on(beacon.latest()).unwrap();
+    assert_eq!(latest.cid(), tixels[2].cid());
+    assert_eq!(rand, extract_randomness(&tixels[2], &tixels[1]).unwrap());
+
+    let (pulse, _) = block_on(beacon.at_index(1)).unwrap();
+    assert_eq!(pulse.cid(), tixels[1].cid());
+    assert!(matches!(block_on(beacon.at_index(0)), Err(RngError::NoRandomness)));
+    assert!(matches!(block_on(beacon.at_index(3)), Err(RngError::NotYetPublished)));
+
+    let (pulse, _) = block_on(beacon.at_round(2)).unwrap();
+    assert_eq!(pulse.cid(), tixels[2].cid());
+    assert!(matches!(block_on(beacon.at_round(3)), Err(RngError::NotYetPublished)));
+
+    let (pulse, _) = block_on(beacon.at(ts(&tixels[1]) + TimeDelta::seconds(30))).unwrap();
+    assert_eq!(pulse.cid(), tixels[1].cid());
+    assert!(matches!(
+      block_on(beacon.at(ts(&tixels[0]) - TimeDelta::seconds(1))),
+      Err(RngError::BeforeGenesis)
+    ));
+    assert!(matches!(
+      block_on(beacon.at(ts(&tixels[2]) + TimeDelta::seconds(60))),
+      Err(RngError::NotYetPublished)
+    ));
+  }
+
+  #[test]
+  fn test_beacon_skipped_round() {
+    let (builder, strand) = builder();
+    let ts = chrono::DateTime::parse_from_rfc3339("2025-02-12T21:11:00Z").unwrap().to_utc();
+    let (first, second) = pulse_pair_at(&builder, strand.clone(), ts, TimeDelta::seconds(120));
+    let beacon = RandomnessBeacon::new(store(&strand, &[first.clone(), second.clone()]), strand.cid());
+
+    let (pulse, rand) = block_on(beacon.at_round(2)).unwrap();
+    assert_eq!(pulse.cid(), second.cid());
+    assert_eq!(rand, extract_randomness_with_gap(&second, &first, 2).unwrap());
+    assert!(matches!(block_on(beacon.at_round(1)), Err(RngError::MissingRound(1))));
+    let (pulse, _) = block_on(beacon.latest()).unwrap();
+    assert_eq!(pulse.cid(), second.cid());
+  }
+
+  #[test]
+  fn test_beacon_audit() {
+    let (builder, strand) = builder();
+    let tixels = chain(&builder, &strand, 3);
+    let sink = SharedSink::default();
+    let beacon = RandomnessBeacon::new(store(&strand, &tixels), strand.cid())
+      .with_audit_sink(sink.clone());
+
+    let (_, rand) = block_on(beacon.latest()).unwrap();
+    block_on(beacon.at_index(1)).unwrap();
+    let events = sink.events();
+    assert_eq!(events.len(), 2);
+    assert!(events.iter().all(|e| e.passed));
+    assert_eq!(events[0].index, 2);
+    assert_eq!(events[0].output, Some(hex::encode(rand)));
+    assert_eq!(events[1].cid, tixels[1].cid().to_string());
+  }
+}
@@ -0,0 +1,52 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use twine_protocol::prelude::*;
+use twine_protocol::twine_lib::Cid;
+
+use crate::{RngError, RngStrandDetails};
+
+/// Strand details cached by a consumer, along with when they were read
+///
+/// Strand details shouldn't be trusted forever: a beacon may be relaunched
+/// under a new strand. Use [`CachedStrandConfig::needs_refresh`] to decide
+/// when to re-resolve the strand and read its details again.
+#[derive(Debug, Clone)]
+pub struct CachedStrandConfig {
+  pub strand_cid: Cid,
+  pub details: RngStrandDetails,
+  pub fetched_at: DateTime<Utc>,
+}
+
+impl CachedStrandConfig {
+  pub fn from_strand(strand: &Strand, now: DateTime<Utc>) -> Result<Self, RngError> {
+    Ok(Self {
+      strand_cid: strand.cid(),
+      details: strand.extract_details::<RngStrandDetails>()?,
+      fetched_at: now,
+    })
+  }
+
+  /// Whether the cached details are at least `max_age` old
+  pub fn needs_refresh(&self, max_age: TimeDelta, now: DateTime<Utc>) -> bool {
+    now - self.fetched_at >= max_age
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::test_util::builder;
+  use super::*;
+
+  #[test]
+  fn test_needs_refresh() {
+    let (_, strand) = builder();
+
+    let fetched_at = DateTime::parse_from_rfc3339("2025-02-12T21:11:00Z").unwrap().to_utc();
+    let config = CachedStrandConfig::from_strand(&strand, fetched_at).unwrap();
+    assert_eq!(config.details.period, TimeDelta::seconds(60));
+
+    let max_age = TimeDelta::hours(1);
+    assert!(!config.needs_refresh(max_age, fetched_at));
+    assert!(!config.needs_refresh(max_age, fetched_at + max_age - TimeDelta::seconds(1)));
+    assert!(config.needs_refresh(max_age, fetched_at + max_age));
+  }
+}
@@ -0,0 +1,144 @@
+use std::path::Path;
+use twine_protocol::prelude::*;
+
+use crate::{extract_randomness_chain, RngError};
+
+/// A conformance test vector for twine-rng implementations
+///
+/// Vectors are stored as JSON. Any implementation can produce them, and
+/// every implementation must agree on the expected output, since beacon
+/// outputs feed applications (such as lotteries) that need exact agreement.
+///
+/// ```json
+/// {
+///   "description": "three pulses on a sha3-256 strand",
+///   "strand": "<tagged dag-json of the strand>",
+///   "tixels": ["<tagged dag-json of each tixel, in order>"],
+///   "expected": ["<hex randomness of tixels[1]>", "<hex randomness of tixels[2]>"]
+/// }
+/// ```
+///
+/// `expected` holds the output of [`crate::extract_randomness`] for every
+/// adjacent pair of tixels, as lowercase hex.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ConformanceVector {
+  pub description: String,
+  pub strand: String,
+  pub tixels: Vec<String>,
+  pub expected: Vec<String>,
+}
+
+impl ConformanceVector {
+  /// Create a vector from a contiguous run of tixels, using this crate's output
+  pub fn from_tixels(description: &str, tixels: &[Twine]) -> Result<Self, RngError> {
+    let first = tixels.first().ok_or(VerificationError::General(
+      "A vector requires at least one tixel".to_string(),
+    ))?;
+    let expected = extract_randomness_chain(tixels)?
+      .iter()
+      .map(hex::encode)
+      .collect();
+    Ok(Self {
+      description: description.to_string(),
+      strand: first.strand().tagged_dag_json(),
+      tixels: tixels.iter().map(|t| t.tixel().tagged_dag_json()).collect(),
+      expected,
+    })
+  }
+
+  /// Decode the tixels of this vector
+  pub fn twines(&self) -> Result<Vec<Twine>, RngError> {
+    let strand = Strand::from_tagged_dag_json(&self.strand)?;
+    self
+      .tixels
+      .iter()
+      .map(|t| Ok(Twine::try_new(strand.clone(), Tixel::from_tagged_dag_json(t)?)?))
+      .collect()
+  }
+
+  /// Check that this crate's output matches the expected output
+  pub fn check(&self) -> Result<(), RngError> {
+    let actual = extract_randomness_chain(&self.twines()?)?;
+    if actual.len() != self.expected.len() {
+      return Err(VerificationError::General(format!(
+        "Expected {} outputs but found {}",
+        self.expected.len(),
+        actual.len()
+      )).into());
+    }
+    for (index, (actual, expected)) in actual.iter().zip(&self.expected).enumerate() {
+      if hex::encode(actual) != expected.to_lowercase() {
+        return Err(RngError::OutputMismatch { index });
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Load every `.json` conformance vector in a directory
+pub fn load_conformance_vectors<P: AsRef<Path>>(
+  dir: P,
+) -> Result<Vec<(String, ConformanceVector)>, Box<dyn std::error::Error>> {
+  let mut vectors = Vec::new();
+  for entry in std::fs::read_dir(dir)? {
+    let path = entry?.path();
+    if path.extension().is_some_and(|ext| ext == "json") {
+      let vector = serde_json::from_slice(&std::fs::read(&path)?)?;
+      vectors.push((path.display().to_string(), vector));
+    }
+  }
+  vectors.sort_by(|a, b| a.0.cmp(&b.0));
+  Ok(vectors)
+}
+
+#[cfg(test)]
+mod test {
+  use chrono::TimeDelta;
+  use twine_protocol::twine_lib::multihash_codetable::Code;
+  use crate::test_util::{builder, builder_with, chain};
+  use crate::*;
+
+  const VECTOR_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/vectors");
+
+  #[test]
+  fn test_vector_roundtrip() {
+    let (builder, strand) = builder();
+    let tixels = chain(&builder, &strand, 2);
+
+    let vector = ConformanceVector::from_tixels("two pulses", &tixels).unwrap();
+    let json = serde_json::to_string(&vector).unwrap();
+    let vector: ConformanceVector = serde_json::from_str(&json).unwrap();
+    vector.check().unwrap();
+
+    let mut tampered = vector.clone();
+    tampered.expected[0] = "00".repeat(32);
+    assert!(matches!(tampered.check(), Err(RngError::OutputMismatch { index: 0 })));
+  }
+
+  #[test]
+  fn test_conformance_vectors() {
+    let vectors = load_conformance_vectors(VECTOR_DIR).unwrap();
+    assert!(!vectors.is_empty(), "No conformance vectors found in {}", VECTOR_DIR);
+    for (path, vector) in vectors {
+      if let Err(e) = vector.check() {
+        panic!("Conformance vector {} ({}) failed: {}", path, vector.description, e);
+      }
+    }
+  }
+
+  /// Regenerate the committed vectors. Only needed when adding vectors:
+  /// existing vectors must keep passing unchanged.
+  #[test]
+  #[ignore = "writes tests/vectors; run with -- --ignored generate_conformance_vectors"]
+  fn generate_conformance_vectors() {
+    for (name, hasher) in [("sha3-256", Code::Sha3_256), ("sha3-512", Code::Sha3_512)] {
+      let (builder, strand) = builder_with(hasher, TimeDelta::seconds(60));
+      let tixels = chain(&builder, &strand, 5);
+      let description = format!("five pulses on a {} strand", name);
+      let vector = ConformanceVector::from_tixels(&description, &tixels).unwrap();
+      vector.check().unwrap();
+      let path = format!("{}/{}.json", VECTOR_DIR, name);
+      std::fs::write(path, serde_json::to_string_pretty(&vector).unwrap()).unwrap();
+    }
+  }
+}
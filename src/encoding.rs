@@ -0,0 +1,125 @@
+use std::fmt::Display;
+use std::str::FromStr;
+use base64::Engine;
+use multibase::Base;
+use crate::RngError;
+
+/// The encodings available for randomness output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutputEncoding {
+  /// Lowercase hexadecimal
+  Hex,
+  /// Standard base64 with padding
+  Base64,
+  /// Uppercase RFC 4648 base32 without padding
+  Base32,
+  /// Multibase string using base32 lower (the same base as CIDs)
+  Multibase,
+  /// The bytes themselves
+  Raw,
+}
+
+impl OutputEncoding {
+  /// Every supported encoding, in a stable order
+  pub const ALL: [OutputEncoding; 5] = [
+    OutputEncoding::Hex,
+    OutputEncoding::Base64,
+    OutputEncoding::Base32,
+    OutputEncoding::Multibase,
+    OutputEncoding::Raw,
+  ];
+
+  /// The name used by [`FromStr`] and [`Display`]
+  pub fn name(&self) -> &'static str {
+    match self {
+      OutputEncoding::Hex => "hex",
+      OutputEncoding::Base64 => "base64",
+      OutputEncoding::Base32 => "base32",
+      OutputEncoding::Multibase => "multibase",
+      OutputEncoding::Raw => "raw",
+    }
+  }
+
+  /// Whether the encoded output is printable text
+  pub fn is_text(&self) -> bool {
+    !matches!(self, OutputEncoding::Raw)
+  }
+}
+
+impl Display for OutputEncoding {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.name())
+  }
+}
+
+impl FromStr for OutputEncoding {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    OutputEncoding::ALL
+      .into_iter()
+      .find(|e| e.name() == s)
+      .ok_or_else(|| format!("Unknown encoding: {}", s))
+  }
+}
+
+/// Encode randomness output
+///
+/// Every encoding except [`OutputEncoding::Raw`] produces ASCII text, so the
+/// result can be turned into a `String` with [`String::from_utf8`].
+pub fn encode_output(bytes: &[u8], encoding: OutputEncoding) -> Vec<u8> {
+  match encoding {
+    OutputEncoding::Hex => hex::encode(bytes).into_bytes(),
+    OutputEncoding::Base64 => base64::engine::general_purpose::STANDARD.encode(bytes).into_bytes(),
+    OutputEncoding::Base32 => Base::Base32Upper.encode(bytes).into_bytes(),
+    OutputEncoding::Multibase => multibase::encode(Base::Base32Lower, bytes).into_bytes(),
+    OutputEncoding::Raw => bytes.to_vec(),
+  }
+}
+
+/// Decode randomness output produced by [`encode_output`]
+///
+/// A multibase string may use any base, not only the one [`encode_output`]
+/// produces.
+pub fn decode_output(encoded: &[u8], encoding: OutputEncoding) -> Result<Vec<u8>, RngError> {
+  let invalid = |e: &dyn Display| RngError::InvalidEncoding(format!("Invalid {} output: {}", encoding, e));
+  if encoding == OutputEncoding::Raw {
+    return Ok(encoded.to_vec());
+  }
+  let text = std::str::from_utf8(encoded).map_err(|e| invalid(&e))?;
+  match encoding {
+    OutputEncoding::Hex => hex::decode(text).map_err(|e| invalid(&e)),
+    OutputEncoding::Base64 => base64::engine::general_purpose::STANDARD.decode(text).map_err(|e| invalid(&e)),
+    OutputEncoding::Base32 => Base::Base32Upper.decode(text).map_err(|e| invalid(&e)),
+    OutputEncoding::Multibase => multibase::decode(text).map(|(_, bytes)| bytes).map_err(|e| invalid(&e)),
+    OutputEncoding::Raw => unreachable!(),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_round_trip() {
+    let bytes: Vec<u8> = (0..=255u8).collect();
+    for encoding in OutputEncoding::ALL {
+      let encoded = encode_output(&bytes, encoding);
+      if encoding.is_text() {
+        assert!(encoded.is_ascii(), "{} output is not text", encoding);
+      }
+      assert_eq!(decode_output(&encoded, encoding).unwrap(), bytes, "{} did not round trip", encoding);
+      assert_eq!(encoding.name().parse::<OutputEncoding>().unwrap(), encoding);
+    }
+  }
+
+  #[test]
+  fn test_known_encodings() {
+    let bytes = b"foobar";
+    assert_eq!(encode_output(bytes, OutputEncoding::Hex), b"666f6f626172");
+    assert_eq!(encode_output(bytes, OutputEncoding::Base64), b"Zm9vYmFy");
+    assert_eq!(encode_output(bytes, OutputEncoding::Base32), b"MZXW6YTBOI");
+    assert_eq!(encode_output(bytes, OutputEncoding::Multibase), b"bmzxw6ytboi");
+    assert!(matches!(decode_output(b"zz", OutputEncoding::Hex), Err(RngError::InvalidEncoding(_))));
+    assert!("base58".parse::<OutputEncoding>().is_err());
+  }
+}
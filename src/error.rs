@@ -0,0 +1,157 @@
+use chrono::TimeDelta;
+use twine_protocol::prelude::{BuildError, VerificationError};
+use twine_protocol::twine_lib::Cid;
+
+/// Errors specific to the twine-rng specification
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RngError {
+  /// The tixels being compared belong to different strands
+  StrandMismatch,
+  /// The current tixel does not link to the given previous tixel
+  PreviousMismatch,
+  /// The current tixel has no previous link, so it is a genesis tixel
+  NoPreviousLink,
+  /// A tixel in a chain failed to validate against its predecessor
+  BrokenLink {
+    /// The index of the offending tixel within the chain
+    index: usize,
+    /// The CID of the offending tixel
+    cid: Cid,
+    source: Box<RngError>,
+  },
+  /// The revealed value does not match the previous precommitment
+  PrecommitmentMismatch,
+  /// The pulse timestamp is not exactly one period after the previous pulse
+  TimestampOutOfPeriod,
+  /// The pulse timestamp does not lie on a period boundary
+  TimestampOffGrid,
+  /// The period declared by the strand differs from the trusted period
+  PeriodMismatch { declared: TimeDelta, trusted: TimeDelta },
+  /// The strand subspec is malformed or names an unsupported version
+  InvalidSubspec(String),
+  /// The strand is signed with an algorithm that isn't provably deterministic
+  UnsupportedSigningAlgorithm,
+  /// The tixel expected to be the genesis pulse links to a previous tixel
+  NotGenesis,
+  /// The genesis pulse has a salt that is not all zeros
+  NonZeroGenesisSalt,
+  /// The genesis precommitment differs from the trusted commitment
+  GenesisCommitmentMismatch,
+  /// The chain does not lead back to the trusted anchor tixel
+  AnchorMismatch,
+  /// The trusted anchor is further behind the target than allowed
+  AnchorTooFar { max_rounds: u64 },
+  /// The revealed value was committed to with a different hasher than expected
+  HasherMismatch,
+  /// The precommitment hash is weaker than required
+  WeakHash { bits: usize, min_bits: usize },
+  /// The pulse provides fewer bits of randomness than required
+  InsufficientEntropy { bits: usize, required: usize },
+  /// An extracted output differs from the expected output
+  OutputMismatch { index: usize },
+  /// No pulse was published for the requested round
+  MissingRound(u64),
+  /// The tixel at a stream position differs from the one recorded
+  PositionMismatch,
+  /// The requested pulse would come before the start of the strand
+  BeforeGenesis,
+  /// The requested pulse has not been published yet
+  NotYetPublished,
+  /// The requested pulse is the genesis pulse, which has no randomness
+  NoRandomness,
+  /// A tixel could not be resolved
+  Resolution(String),
+  /// More output was requested than HKDF can expand the randomness to
+  OutputTooLong { requested: usize },
+  /// Encoded output could not be decoded
+  InvalidEncoding(String),
+  /// Any other verification error
+  Underlying(VerificationError),
+}
+
+impl std::fmt::Display for RngError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      RngError::StrandMismatch => write!(f, "Current tixel and previous tixel are on different strands"),
+      RngError::PreviousMismatch => write!(f, "Previous tixel does not match current tixel's previous link"),
+      RngError::NoPreviousLink => write!(f, "Current tixel has no previous link"),
+      RngError::BrokenLink { index, cid, source } => write!(f, "Broken link at index {} ({}): {}", index, cid, source),
+      RngError::PrecommitmentMismatch => write!(f, "Precommitment does not match hash of revealed value"),
+      RngError::TimestampOutOfPeriod => write!(f, "Timestamps are not within one period of each other"),
+      RngError::TimestampOffGrid => write!(f, "Timestamp is not aligned to a period boundary"),
+      RngError::PeriodMismatch { declared, trusted } => write!(f, "Declared strand period {} does not match trusted period {}", declared, trusted),
+      RngError::InvalidSubspec(reason) => write!(f, "Invalid subspec: {}", reason),
+      RngError::UnsupportedSigningAlgorithm => write!(f, "Signature algorithm must be provably deterministic"),
+      RngError::NotGenesis => write!(f, "Tixel is not the first tixel of its strand"),
+      RngError::NonZeroGenesisSalt => write!(f, "Genesis salt must be all zeros"),
+      RngError::GenesisCommitmentMismatch => write!(f, "Genesis precommitment does not match the trusted commitment"),
+      RngError::AnchorMismatch => write!(f, "Chain does not lead back to the trusted anchor"),
+      RngError::AnchorTooFar { max_rounds } => write!(f, "Anchor is more than {} rounds behind the target", max_rounds),
+      RngError::HasherMismatch => write!(f, "Commitment was not made with the given hasher"),
+      RngError::WeakHash { bits, min_bits } => write!(f, "Precommitment hash provides {} bits but at least {} are required", bits, min_bits),
+      RngError::InsufficientEntropy { bits, required } => write!(f, "Pulse provides {} bits of randomness but {} were required", bits, required),
+      RngError::OutputMismatch { index } => write!(f, "Output {} does not match the expected output", index),
+      RngError::MissingRound(round) => write!(f, "No pulse was published for round {}", round),
+      RngError::PositionMismatch => write!(f, "Tixel at the stream position does not match the recorded position"),
+      RngError::BeforeGenesis => write!(f, "Requested pulse is before the start of the strand"),
+      RngError::NotYetPublished => write!(f, "Requested pulse has not been published yet"),
+      RngError::NoRandomness => write!(f, "The genesis pulse has no randomness"),
+      RngError::Resolution(e) => write!(f, "Failed to resolve tixel: {}", e),
+      RngError::OutputTooLong { requested } => write!(f, "Unable to expand randomness to {} bytes", requested),
+      RngError::InvalidEncoding(reason) => write!(f, "{}", reason),
+      RngError::Underlying(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl std::error::Error for RngError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      RngError::BrokenLink { source, .. } => Some(source.as_ref()),
+      RngError::Underlying(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+impl From<VerificationError> for RngError {
+  fn from(e: VerificationError) -> Self {
+    RngError::Underlying(e)
+  }
+}
+
+impl From<RngError> for VerificationError {
+  fn from(e: RngError) -> Self {
+    match e {
+      RngError::Underlying(e) => e,
+      RngError::StrandMismatch
+      | RngError::PreviousMismatch
+      | RngError::NoPreviousLink
+      | RngError::BrokenLink { .. }
+      | RngError::AnchorMismatch
+      | RngError::AnchorTooFar { .. }
+      | RngError::MissingRound(_)
+      | RngError::PositionMismatch
+      | RngError::NotGenesis
+      | RngError::BeforeGenesis
+      | RngError::NotYetPublished
+      | RngError::NoRandomness
+      | RngError::Resolution(_)
+      | RngError::OutputTooLong { .. }
+      | RngError::InvalidEncoding(_) => {
+        VerificationError::General(e.to_string())
+      }
+      _ => VerificationError::Payload(e.to_string()),
+    }
+  }
+}
+
+impl From<RngError> for BuildError {
+  fn from(e: RngError) -> Self {
+    match e {
+      RngError::UnsupportedSigningAlgorithm => BuildError::PayloadConstruction(e.to_string()),
+      e => BuildError::BadData(e.into()),
+    }
+  }
+}
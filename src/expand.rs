@@ -0,0 +1,61 @@
+use hkdf::Hkdf;
+use sha2::{Sha256, Sha512};
+use crate::RngError;
+
+/// Expand randomness into an arbitrary amount of output with HKDF
+///
+/// The randomness is used as the HKDF input key material with no salt, and
+/// `info` provides domain separation: different `info` values yield
+/// independent outputs. HKDF-SHA-256 is used for randomness of up to 32
+/// bytes (e.g. from a Sha3_256 strand) and HKDF-SHA-512 for anything
+/// longer (e.g. from a Sha3_512 strand), so the output is reproducible by
+/// anyone with a standard HKDF implementation.
+///
+/// HKDF can produce at most 255 hash lengths of output (8160 bytes with
+/// SHA-256, 16320 with SHA-512). Requesting more is an error.
+pub fn expand_randomness(
+  randomness: &[u8],
+  info: &[u8],
+  out_len: usize,
+) -> Result<Vec<u8>, RngError> {
+  let mut out = vec![0u8; out_len];
+  let result = if randomness.len() > 32 {
+    Hkdf::<Sha512>::new(None, randomness).expand(info, &mut out)
+  } else {
+    Hkdf::<Sha256>::new(None, randomness).expand(info, &mut out)
+  };
+  result.map_err(|_| RngError::OutputTooLong { requested: out_len })?;
+  Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_rfc5869_vector() {
+    // RFC 5869 test case 3 (SHA-256, no salt, no info)
+    let ikm = [0x0bu8; 22];
+    let okm = expand_randomness(&ikm, &[], 42).unwrap();
+    assert_eq!(
+      okm,
+      hex::decode("8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8").unwrap()
+    );
+  }
+
+  #[test]
+  fn test_domain_separation() {
+    let rand = [9u8; 64];
+    let a = expand_randomness(&rand, b"lottery", 100).unwrap();
+    assert_eq!(a, expand_randomness(&rand, b"lottery", 100).unwrap());
+    assert_ne!(a, expand_randomness(&rand, b"audit", 100).unwrap());
+  }
+
+  #[test]
+  fn test_length_limit() {
+    assert!(expand_randomness(&[1u8; 32], &[], 255 * 32).is_ok());
+    assert!(matches!(expand_randomness(&[1u8; 32], &[], 255 * 32 + 1), Err(RngError::OutputTooLong { requested: 8161 })));
+    assert!(expand_randomness(&[1u8; 64], &[], 255 * 64).is_ok());
+    assert!(expand_randomness(&[1u8; 64], &[], 255 * 64 + 1).is_err());
+  }
+}
@@ -1,7 +1,11 @@
 #![doc = include_str!("../README.md")]
 
 use chrono::TimeDelta;
-use twine_protocol::{prelude::*, twine_lib::{ipld_core::serde::from_ipld, multihash_codetable::{Code, Multihash}, semver::VersionReq}};
+use rand::{CryptoRng, RngCore};
+use twine_protocol::{prelude::*, twine_lib::{ipld_core::serde::from_ipld, multihash_codetable::{Code, Multihash}}};
+
+mod error;
+pub use error::*;
 
 mod payload;
 pub use payload::*;
@@ -9,7 +13,55 @@ pub use payload::*;
 mod timing;
 pub use timing::*;
 
+mod period;
+pub use period::{format_period, parse_period};
+
 mod validations;
+pub use validations::{is_deterministic_signing_algorithm, validate_subspec_string};
+
+mod audit;
+pub use audit::*;
+
+mod resolution;
+pub use resolution::*;
+
+mod stream;
+pub use stream::*;
+
+mod beacon;
+pub use beacon::*;
+
+mod verifier;
+pub use verifier::*;
+
+mod warnings;
+pub use warnings::*;
+
+mod rng;
+pub use rng::*;
+
+mod conformance;
+pub use conformance::*;
+
+mod expand;
+pub use expand::*;
+
+mod randomness;
+pub use randomness::*;
+
+mod encoding;
+pub use encoding::*;
+
+mod config;
+pub use config::*;
+
+#[cfg(test)]
+mod test_util;
+
+#[cfg(feature = "transparency")]
+mod transparency;
+#[cfg(feature = "transparency")]
+pub use transparency::*;
 
 /// The prefix for the twine-rng specification
 pub const SPEC_PREFIX : &str = "twine-rng";
@@ -24,9 +76,26 @@ pub fn subspec_string() -> String {
 /// The strand details for the twine-rng specification
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct RngStrandDetails {
+  /// The time between pulses
+  ///
+  /// Stored as a [`TimeDelta`], as twine-rng/1.0.0 specifies. An ISO-8601
+  /// duration (e.g. `"PT1M"`) is also accepted when reading.
+  ///
+  /// Must be a positive whole number of seconds.
+  #[serde(with = "period")]
   pub period: TimeDelta,
 }
 
+impl RngStrandDetails {
+  /// The resolution that pulse timestamps must be aligned to
+  ///
+  /// Periods are a whole number of seconds, so every pulse lands on a
+  /// whole second.
+  pub fn granularity(&self) -> TimeDelta {
+    TimeDelta::seconds(1)
+  }
+}
+
 /// A builder to aid in constructing payloads for the twine-rng specification
 ///
 /// # Example
@@ -42,6 +111,7 @@ pub struct RngStrandDetails {
 pub struct PayloadBuilder {
   current: Vec<u8>,
   next: Vec<u8>,
+  tolerance: TimeDelta,
 }
 
 impl PayloadBuilder {
@@ -49,7 +119,30 @@ impl PayloadBuilder {
   ///
   /// When starting from the beginning of a strand, the `current` value will be ignored
   pub fn new(current: Vec<u8>, next: Vec<u8>) -> Self {
-    Self { current, next }
+    Self { current, next, tolerance: TimeDelta::zero() }
+  }
+
+  /// Tolerate publishing up to `tolerance` late without skipping a round
+  ///
+  /// A pulse built less than `tolerance` after it was due still gets the
+  /// timestamp exactly one period after the previous pulse, instead of
+  /// jumping to the next round. The timestamp stays on the grid, so the
+  /// pulse verifies as usual. Defaults to zero.
+  pub fn with_tolerance(self, tolerance: TimeDelta) -> Self {
+    Self { tolerance, ..self }
+  }
+
+  pub fn tolerance(&self) -> TimeDelta {
+    self.tolerance
+  }
+
+  /// Create a new payload builder for the start of a strand, using secure randomness
+  ///
+  /// The random value is drawn from the operating system and sized to the
+  /// digest size of `code`, which must be the strand's hasher.
+  pub fn new_random(code: Code) -> Self {
+    let next = Self::prepare_batch(&mut rand::rngs::OsRng, 1, code).remove(0);
+    Self::new(vec![0u8; next.len()], next)
   }
 
   pub fn current(&self) -> &[u8] {
@@ -62,18 +155,49 @@ impl PayloadBuilder {
   }
 
   pub fn advance(self, next: Vec<u8>) -> Self {
-    Self::new(self.next, next)
+    Self::new(self.next, next).with_tolerance(self.tolerance)
+  }
+
+  /// Advance the payload with new secure randomness
+  ///
+  /// The new value has the same size as the previous one, so it always
+  /// matches the hasher the builder was created for.
+  pub fn advance_random(self) -> Self {
+    let mut next = vec![0u8; self.next.len()];
+    rand::rngs::OsRng.fill_bytes(&mut next);
+    self.advance(next)
+  }
+
+  /// Generate the random values for the next `n` pulses in one batch
+  ///
+  /// Each value is sized to the digest size of `code`, so it will match the
+  /// precommitment width of a strand using that hasher. The values should be
+  /// fed to [`PayloadBuilder::advance`] in the order they are returned.
+  pub fn prepare_batch<R: RngCore + CryptoRng>(rng: &mut R, n: usize, code: Code) -> Vec<Vec<u8>> {
+    use twine_protocol::twine_lib::multihash_codetable::MultihashDigest;
+    let len = code.digest(&[]).size() as usize;
+    (0..n)
+      .map(|_| {
+        let mut bytes = vec![0u8; len];
+        rng.fill_bytes(&mut bytes);
+        bytes
+      })
+      .collect()
   }
 
   pub fn builder(&self) -> impl Fn(&Strand, Option<&Twine>) -> Result<RandomnessPayload, BuildError> + '_ {
+    self.builder_with_clock(&SystemClock)
+  }
+
+  /// Same as [`PayloadBuilder::builder`], but timing pulses with `clock`
+  pub fn builder_with_clock<'a>(
+    &'a self,
+    clock: &'a dyn Clock,
+  ) -> impl Fn(&Strand, Option<&Twine>) -> Result<RandomnessPayload, BuildError> + 'a {
     move |strand: &Strand, prev: Option<&Twine>| {
       validations::validate_signing_algorithm(strand.key().alg)?;
       let subspec = strand.subspec().ok_or(BuildError::PayloadConstruction("Subspec is required for validation".to_string()))?;
-      validations::validate_subspec(&subspec)?;
-
-      if !subspec.satisfies(VersionReq::parse("1.0.*").unwrap()) {
-        return Err(BuildError::BadData(VerificationError::Payload("Unable to build payload for future version".to_string())));
-      }
+      validate_subspec_string(&subspec.to_string())?;
 
       let details: RngStrandDetails = from_ipld(strand.details().clone())
         .map_err(|_| BuildError::BadData(VerificationError::Payload("Invalid strand details".to_string())))?;
@@ -83,11 +207,18 @@ impl PayloadBuilder {
 
       match prev {
         None => {
-          let payload = RandomnessPayload::new_start(pre, period)?;
+          let payload = RandomnessPayload::new_start_with_clock(pre, period, clock)?;
           Ok(payload)
         }
         Some(prev) => {
-          let payload = RandomnessPayload::new_next(self.current(), pre, prev.tixel(), period)?;
+          let payload = RandomnessPayload::new_next_with_tolerance(
+            self.current(),
+            pre,
+            prev.tixel(),
+            period,
+            self.tolerance,
+            clock,
+          )?;
           Ok(payload)
         }
       }
@@ -95,58 +226,418 @@ impl PayloadBuilder {
   }
 }
 
+fn check_link(current: &Twine, prev: &Twine) -> Result<(), RngError> {
+  if current.strand_cid() != prev.strand_cid() {
+    return Err(RngError::StrandMismatch);
+  }
+  match current.previous() {
+    None => return Err(RngError::NoPreviousLink),
+    Some(p) => {
+      if prev != &p {
+        return Err(RngError::PreviousMismatch);
+      }
+    }
+  };
+  Ok(())
+}
+
+/// Validate that a tixel is a well formed genesis pulse
+///
+/// The first pulse of a strand anchors every later verification, so an
+/// audit should check it explicitly. It must not link to a previous tixel,
+/// its salt must be all zeros (the salt length already matches the `pre`
+/// size for any valid payload), and its timestamp must be aligned to the
+/// strand period.
+pub fn validate_genesis(first: &Twine) -> Result<(), RngError> {
+  if first.previous().is_some() {
+    return Err(RngError::NotGenesis);
+  }
+  let payload = first.extract_payload::<RandomnessPayload>()?;
+  if payload.salt().iter().any(|b| *b != 0) {
+    return Err(RngError::NonZeroGenesisSalt);
+  }
+  let details = first.strand().extract_details::<RngStrandDetails>()?;
+  timing::verify_timestamp_against_details(payload.timestamp(), &details)?;
+  payload.validate_grid_alignment(details.period)
+}
+
 /// Safely extract the randomness from a twine pair
 ///
 /// This function performs necessary validations to ensure the randomness is valid
 pub fn extract_randomness(
   current: &Twine,
   prev: &Twine,
-) -> Result<Vec<u8>, VerificationError> {
-  if current.strand_cid() != prev.strand_cid() {
-    return Err(VerificationError::General(
-      "Current tixel and previous tixel are on different strands".to_string(),
-    ));
+) -> Result<Vec<u8>, RngError> {
+  extract_randomness_typed(current, prev).map(Randomness::into_bytes)
+}
+
+/// Same as [`extract_randomness`], but keeping track of which pulse the
+/// randomness came from
+pub fn extract_randomness_typed(
+  current: &Twine,
+  prev: &Twine,
+) -> Result<Randomness, RngError> {
+  checked_randomness(current, prev, |payload| payload.validate_randomness(prev))
+}
+
+fn checked_randomness(
+  current: &Twine,
+  prev: &Twine,
+  validate: impl FnOnce(&RandomnessPayload) -> Result<(), RngError>,
+) -> Result<Randomness, RngError> {
+  check_link(current, prev)?;
+  let subspec = current.strand().subspec()
+    .ok_or(RngError::InvalidSubspec("Subspec is missing".to_string()))?;
+  validate_subspec_string(&subspec.to_string())?;
+  let payload = current.extract_payload::<RandomnessPayload>()?;
+  validate(&payload)?;
+  Ok(Randomness::new(
+    current.cid().hash().digest().to_vec(),
+    current.cid(),
+    current.strand_cid(),
+    payload.timestamp(),
+  ))
+}
+
+/// Extract the randomness from a twine pair, checking against a trusted period
+///
+/// Use this when the period is known from a source other than the strand
+/// itself (e.g. a published beacon configuration). A strand declaring any
+/// other period is rejected, even if its timestamps are consistent with it.
+pub fn extract_randomness_with_period(
+  current: &Twine,
+  prev: &Twine,
+  trusted_period: TimeDelta,
+) -> Result<Vec<u8>, RngError> {
+  extract_randomness_typed_with_period(current, prev, trusted_period).map(Randomness::into_bytes)
+}
+
+/// Same as [`extract_randomness_with_period`], but returning a [`Randomness`]
+pub fn extract_randomness_typed_with_period(
+  current: &Twine,
+  prev: &Twine,
+  trusted_period: TimeDelta,
+) -> Result<Randomness, RngError> {
+  checked_randomness(current, prev, |payload| payload.validate_randomness_with_period(prev, trusted_period))
+}
+
+/// Extract the randomness from a twine pair, tolerating skipped pulses
+///
+/// See [`RandomnessPayload::validate_randomness_with_gap`].
+pub fn extract_randomness_with_gap(
+  current: &Twine,
+  prev: &Twine,
+  max_gap: u32,
+) -> Result<Vec<u8>, RngError> {
+  extract_randomness_typed_with_gap(current, prev, max_gap).map(Randomness::into_bytes)
+}
+
+/// Same as [`extract_randomness_with_gap`], but returning a [`Randomness`]
+pub fn extract_randomness_typed_with_gap(
+  current: &Twine,
+  prev: &Twine,
+  max_gap: u32,
+) -> Result<Randomness, RngError> {
+  checked_randomness(current, prev, |payload| payload.validate_randomness_with_gap(prev, max_gap))
+}
+
+/// Safely extract the randomness from a twine pair, accepting timestamps
+/// up to `tolerance` away from the expected grid point
+///
+/// See [`RandomnessPayload::validate_randomness_with_tolerance`].
+pub fn extract_randomness_with_tolerance(
+  current: &Twine,
+  prev: &Twine,
+  tolerance: TimeDelta,
+) -> Result<Vec<u8>, RngError> {
+  extract_randomness_typed_with_tolerance(current, prev, tolerance).map(Randomness::into_bytes)
+}
+
+/// Same as [`extract_randomness_with_tolerance`], but returning a [`Randomness`]
+pub fn extract_randomness_typed_with_tolerance(
+  current: &Twine,
+  prev: &Twine,
+  tolerance: TimeDelta,
+) -> Result<Randomness, RngError> {
+  checked_randomness(current, prev, |payload| payload.validate_randomness_with_tolerance(prev, tolerance))
+}
+
+/// Compare the periods of two versions of a strand
+///
+/// Returns the old and new periods if they differ, or `None` if the new
+/// strand runs at the same rate. This is useful when following a beacon
+/// that has been relaunched under a new strand.
+pub fn compare_periods(
+  old: &Strand,
+  new: &Strand,
+) -> Result<Option<(TimeDelta, TimeDelta)>, RngError> {
+  let old_period = old.extract_details::<RngStrandDetails>()?.period;
+  let new_period = new.extract_details::<RngStrandDetails>()?.period;
+  if old_period == new_period {
+    Ok(None)
+  } else {
+    Ok(Some((old_period, new_period)))
   }
-  match current.previous() {
-    None => {
-      return Err(VerificationError::General(
-        "Current tixel has no previous link".to_string(),
-      ));
+}
+
+/// The output size in bits of a supported multihash code
+pub fn hash_strength_bits(code: u64) -> Result<usize, RngError> {
+  use twine_protocol::twine_lib::multihash_codetable::MultihashDigest;
+  let code = Code::try_from(code).map_err(|_| VerificationError::UnsupportedHashAlgorithm)?;
+  Ok(code.digest(&[]).size() as usize * 8)
+}
+
+/// Ensure a payload's precommitment uses a hash of at least `min_bits` output
+///
+/// This lets a consumer reject beacons using weak hashes without
+/// depending on a specific hasher.
+pub fn require_min_hash_strength(
+  payload: &RandomnessPayload,
+  min_bits: usize,
+) -> Result<(), RngError> {
+  let bits = hash_strength_bits(payload.pre().code())?;
+  if bits < min_bits {
+    return Err(RngError::WeakHash { bits, min_bits });
+  }
+  Ok(())
+}
+
+/// Verify that revealed entropy matches a commitment published outside of twine
+///
+/// For operators who also anchor commitments elsewhere (e.g. a transparency
+/// log), this checks that `revealed` hashes to `external_commitment` with
+/// `hasher`. No tixels are involved.
+pub fn verify_external_commitment(
+  revealed: &[u8],
+  external_commitment: &Multihash,
+  hasher: Code,
+) -> Result<(), RngError> {
+  if u64::from(hasher) != external_commitment.code() {
+    return Err(RngError::HasherMismatch);
+  }
+  RandomnessPayload::verify_commitment(external_commitment, revealed)
+}
+
+/// Compute the salt the next pulse must carry, given the value it will reveal
+///
+/// Once the value committed to by `prev` is known, the salt of the next
+/// pulse is fully determined. The next pulse's output (its CID) is not,
+/// since it also covers the next pulse's own precommitment and signature,
+/// which only the producer knows in advance. This is still enough for a
+/// consumer to check a pre-announced reveal before the pulse is published.
+///
+/// `hasher` must be the strand's hasher, and `revealed_next` must match the
+/// precommitment of `prev` (see [`RandomnessPayload::verify_precommitment`]).
+pub fn anticipate_salt(
+  prev: &Twine,
+  revealed_next: &[u8],
+  hasher: Code,
+) -> Result<Vec<u8>, RngError> {
+  let prev_payload = prev.extract_payload::<RandomnessPayload>()?;
+  if u64::from(hasher) != prev_payload.pre().code() {
+    return Err(RngError::HasherMismatch);
+  }
+  prev_payload.verify_precommitment(revealed_next)?;
+  Ok(
+    revealed_next
+      .iter()
+      .zip(prev.cid().hash().digest().iter())
+      .map(|(a, b)| a ^ b)
+      .collect(),
+  )
+}
+
+/// Ensure a pulse provides at least `k` bits of randomness
+///
+/// The pulse is validated as in [`extract_randomness`], then the size of its
+/// output is checked. Use this before deriving a `k` bit decision from the
+/// output, so that no more bits are taken than the beacon actually provides.
+pub fn assert_min_entropy_bits(
+  current: &Twine,
+  prev: &Twine,
+  k: usize,
+) -> Result<(), RngError> {
+  let rand = extract_randomness(current, prev)?;
+  let bits = rand.len() * 8;
+  if bits < k {
+    return Err(RngError::InsufficientEntropy { bits, required: k });
+  }
+  Ok(())
+}
+
+/// Safely extract the randomness from a contiguous run of tixels
+///
+/// The tixels must be in order, each linking to the one before it on the
+/// same strand. Every adjacent pair is validated as in [`extract_randomness`].
+/// The first tixel (for example the genesis tixel) has no previous tixel in
+/// the slice, so it yields no randomness: the returned vector holds the
+/// randomness of `tixels[1..]`, in order.
+///
+/// If a link is broken, [`RngError::BrokenLink`] reports the index of the
+/// first tixel that fails to validate against its predecessor.
+pub fn extract_randomness_chain(
+  tixels: &[Twine],
+) -> Result<Vec<Vec<u8>>, RngError> {
+  extract_randomness_chain_typed(tixels)
+    .map(|rands| rands.into_iter().map(Randomness::into_bytes).collect())
+}
+
+/// Same as [`extract_randomness_chain`], but returning a [`Randomness`] per pulse
+pub fn extract_randomness_chain_typed(
+  tixels: &[Twine],
+) -> Result<Vec<Randomness>, RngError> {
+  checked_chain(tixels, extract_randomness_typed)
+}
+
+/// Same as [`extract_randomness_chain`], but tolerating skipped pulses
+///
+/// Each pair is validated as in [`extract_randomness_with_gap`].
+pub fn extract_randomness_chain_with_gap(
+  tixels: &[Twine],
+  max_gap: u32,
+) -> Result<Vec<Vec<u8>>, RngError> {
+  extract_randomness_chain_typed_with_gap(tixels, max_gap)
+    .map(|rands| rands.into_iter().map(Randomness::into_bytes).collect())
+}
+
+/// Same as [`extract_randomness_chain_with_gap`], but returning a [`Randomness`] per pulse
+pub fn extract_randomness_chain_typed_with_gap(
+  tixels: &[Twine],
+  max_gap: u32,
+) -> Result<Vec<Randomness>, RngError> {
+  checked_chain(tixels, |current, prev| extract_randomness_typed_with_gap(current, prev, max_gap))
+}
+
+fn checked_chain(
+  tixels: &[Twine],
+  extract: impl Fn(&Twine, &Twine) -> Result<Randomness, RngError>,
+) -> Result<Vec<Randomness>, RngError> {
+  tixels
+    .windows(2)
+    .enumerate()
+    .map(|(i, pair)| {
+      extract(&pair[1], &pair[0]).map_err(|e| RngError::BrokenLink {
+        index: i + 1,
+        cid: pair[1].cid(),
+        source: Box::new(e),
+      })
+    })
+    .collect()
+}
+
+/// Verify a chain of tixels against their strand in parallel, then extract its randomness
+///
+/// Checking each tixel's signature against the strand key (as
+/// [`Twine::try_new`] does) dominates the cost of verifying a chain, and
+/// doesn't depend on the order of the tixels, so it is spread across threads.
+/// The cheap link and payload checks then run in order, as in
+/// [`extract_randomness_chain`], so the output is identical to it.
+///
+/// If a tixel fails to verify against the strand, [`RngError::BrokenLink`]
+/// reports the index of the first one that does.
+#[cfg(feature = "rayon")]
+pub fn extract_randomness_chain_par(
+  strand: &Strand,
+  tixels: &[Tixel],
+) -> Result<Vec<Vec<u8>>, RngError> {
+  use rayon::prelude::*;
+  let twines = tixels
+    .par_iter()
+    .enumerate()
+    .map(|(i, tixel)| verifier::verify_link(&DefaultTixelVerifier, strand, i, tixel))
+    .collect::<Result<Vec<_>, _>>()?;
+  extract_randomness_chain(&twines)
+}
+
+/// Verify that every gap in a run of tixels is exactly the strand period
+///
+/// The period is read from `strand` once, rather than from each tixel's
+/// strand as [`RandomnessPayload::validate_randomness`] does. Every tixel
+/// must belong to `strand`. A [`RngError::BrokenLink`] reports the position
+/// in `tixels` of the first tixel that is on another strand or whose
+/// timestamp deviates from the period.
+pub fn verify_range_period(
+  tixels: &[Twine],
+  strand: &Strand,
+) -> Result<(), RngError> {
+  let period = strand.extract_details::<RngStrandDetails>()?.period;
+  let mut prev_time = None;
+  for (index, tixel) in tixels.iter().enumerate() {
+    let broken = |e: RngError| RngError::BrokenLink {
+      index,
+      cid: tixel.cid(),
+      source: Box::new(e),
+    };
+    if tixel.strand_cid() != strand.cid() {
+      return Err(broken(RngError::StrandMismatch));
     }
-    Some(p) => {
-      if prev != &p {
-        return Err(VerificationError::General(
-          "Previous tixel does not match current tixel's previous link".to_string(),
-        ));
+    let timestamp = tixel.extract_payload::<RandomnessPayload>()?.timestamp();
+    if let Some(prev_time) = prev_time {
+      if timestamp - prev_time != period {
+        return Err(broken(RngError::TimestampOutOfPeriod));
       }
     }
-  };
+    prev_time = Some(timestamp);
+  }
+  Ok(())
+}
 
-  let payload = current.extract_payload::<RandomnessPayload>()?;
-  if let Err(e) = payload.validate_randomness(prev) {
-    return Err(e);
+/// Produce the commit-reveal transcript for a contiguous run of tixels
+///
+/// For each tixel, the result contains the precommitment it published and
+/// the value it revealed for the previous tixel's precommitment. The first
+/// tixel's previous commitment is not part of the slice, so its revealed
+/// value is empty. Every pair is verified before the transcript is returned.
+pub fn reveal_transcript(
+  tixels: &[Twine],
+) -> Result<Vec<(Multihash, Vec<u8>)>, RngError> {
+  extract_randomness_chain(tixels)?;
+  tixels
+    .iter()
+    .enumerate()
+    .map(|(i, tixel)| {
+      let payload = tixel.extract_payload::<RandomnessPayload>()?;
+      let revealed = match i {
+        0 => Vec::new(),
+        _ => payload.local_random_value(&tixels[i - 1]),
+      };
+      Ok((payload.pre().clone(), revealed))
+    })
+    .collect()
+}
+
+/// Create a seeded [`StdRng`](rand::rngs::StdRng) from a contiguous run of tixels
+///
+/// The run is verified, then the randomness of every pair (as returned by
+/// [`extract_randomness`]) is concatenated in order and hashed with SHA3-256
+/// to form the 32 byte seed. Anyone with the same tixels can reproduce the
+/// seed. Note that the algorithm behind `StdRng` may change between versions
+/// of `rand`, so the seed is the reproducible artifact, not the stream.
+pub fn seed_stdrng_from_range(
+  tixels: &[Twine],
+) -> Result<rand::rngs::StdRng, RngError> {
+  use rand::SeedableRng;
+  use twine_protocol::twine_lib::multihash_codetable::MultihashDigest;
+  let outputs = extract_randomness_chain(tixels)?;
+  if outputs.is_empty() {
+    return Err(VerificationError::General(
+      "At least two tixels are required to seed an rng".to_string(),
+    ).into());
   }
-  Ok(current.cid().hash().digest().to_vec())
+  let seed: [u8; 32] = Code::Sha3_256
+    .digest(&outputs.concat())
+    .digest()
+    .try_into()
+    .expect("Sha3_256 digest is 32 bytes");
+  Ok(rand::rngs::StdRng::from_seed(seed))
 }
 
 #[cfg(test)]
 mod test {
   use twine_protocol::twine_builder::RingSigner;
+  use crate::test_util::{builder, builder_with, builder_with_signer, chain, pulse_pair_at, strand_with};
   use crate::*;
 
-  fn builder() -> (TwineBuilder<2, RingSigner>, Strand) {
-    let signer = RingSigner::generate_rs256(2048).unwrap();
-    let builder = TwineBuilder::new(signer);
-    let strand = builder.build_strand()
-      .subspec(subspec_string())
-      .hasher(Code::Sha3_256)
-      .details(RngStrandDetails { period: TimeDelta::seconds(60) })
-      .done()
-      .unwrap();
-
-    (builder, strand)
-  }
-
   #[test]
   fn test_builder() {
     let (builder, strand) = builder();
@@ -175,6 +666,71 @@ mod test {
     payload.validate_randomness(&first).unwrap();
   }
 
+  #[test]
+  fn test_reject_bad_subspec() {
+    let (builder, _) = builder();
+    let strand = strand_with(&builder, "twine-rng/2.0.0".to_string(), Code::Sha3_256, TimeDelta::seconds(60));
+    let pb = PayloadBuilder::new([0u8; 32].to_vec(), [1u8; 32].to_vec());
+    assert!(builder.build_first(strand)
+      .build_payload_then_done(pb.builder())
+      .is_err());
+
+    // built by hand, so only extraction sees the subspec
+    let strand = strand_with(&builder, "twine-rnd/1.0.0".to_string(), Code::Sha3_256, TimeDelta::seconds(60));
+    let ts = chrono::DateTime::parse_from_rfc3339("2025-02-12T21:11:00Z").unwrap().to_utc();
+    let (first, second) = pulse_pair_at(&builder, strand, ts, TimeDelta::seconds(60));
+    assert!(matches!(extract_randomness_typed(&second, &first), Err(RngError::InvalidSubspec(_))));
+  }
+
+  #[test]
+  fn test_builder_with_clock() {
+    let (builder, strand) = builder();
+    let ts = chrono::DateTime::parse_from_rfc3339("2025-02-12T21:11:00Z").unwrap().to_utc();
+    let pb = PayloadBuilder::new([0u8; 32].to_vec(), [1u8; 32].to_vec());
+    let clock = FixedClock(ts - TimeDelta::seconds(30));
+    let first = builder.build_first(strand)
+      .build_payload_then_done(pb.builder_with_clock(&clock))
+      .unwrap();
+    let payload = first.extract_payload::<RandomnessPayload>().unwrap();
+    assert_eq!(payload.timestamp(), ts);
+
+    let pb = pb.advance([2u8; 32].to_vec());
+    let clock = FixedClock(ts + TimeDelta::seconds(10));
+    let second = builder.build_next(&first)
+      .build_payload_then_done(pb.builder_with_clock(&clock))
+      .unwrap();
+    let payload = second.extract_payload::<RandomnessPayload>().unwrap();
+    assert_eq!(payload.timestamp(), ts + TimeDelta::seconds(60));
+    extract_randomness(&second, &first).unwrap();
+  }
+
+  #[test]
+  fn test_builder_with_tolerance() {
+    let (builder, strand) = builder();
+    let ts = chrono::DateTime::parse_from_rfc3339("2025-02-12T21:11:00Z").unwrap().to_utc();
+    let pb = PayloadBuilder::new([0u8; 32].to_vec(), [1u8; 32].to_vec());
+    assert_eq!(pb.tolerance(), TimeDelta::zero());
+    let first = builder.build_first(strand)
+      .build_payload_then_done(pb.builder_with_clock(&FixedClock(ts - TimeDelta::seconds(30))))
+      .unwrap();
+
+    // three seconds late
+    let clock = FixedClock(ts + TimeDelta::seconds(63));
+    let pb = pb.advance([2u8; 32].to_vec());
+    let skipped = builder.build_next(&first)
+      .build_payload_then_done(pb.builder_with_clock(&clock))
+      .unwrap();
+    assert_eq!(skipped.extract_payload::<RandomnessPayload>().unwrap().timestamp(), ts + TimeDelta::seconds(120));
+
+    let pb = pb.with_tolerance(TimeDelta::seconds(5));
+    let second = builder.build_next(&first)
+      .build_payload_then_done(pb.builder_with_clock(&clock))
+      .unwrap();
+    assert_eq!(second.extract_payload::<RandomnessPayload>().unwrap().timestamp(), ts + TimeDelta::seconds(60));
+    extract_randomness(&second, &first).unwrap();
+    assert_eq!(pb.advance([3u8; 32].to_vec()).tolerance(), TimeDelta::seconds(5));
+  }
+
   #[test]
   fn test_bad_construction() {
     let (builder, strand) = builder();
@@ -222,38 +778,23 @@ mod test {
       .build_payload_then_done(pb2.builder())
       .unwrap();
 
-    assert!(extract_randomness(&second_2, &first_1).is_err());
-    assert!(extract_randomness(&second_1, &first_2).is_err());
+    assert!(matches!(extract_randomness(&second_2, &first_1), Err(RngError::PreviousMismatch)));
+    assert!(matches!(extract_randomness(&second_1, &first_2), Err(RngError::PreviousMismatch)));
+    assert!(matches!(extract_randomness(&first_1, &first_2), Err(RngError::NoPreviousLink)));
   }
 
   #[test]
-  fn test_reject_bad_signing_key() {
+  fn test_ed25519_signing_key() {
     let signer = RingSigner::generate_ed25519().unwrap();
-    let builder = TwineBuilder::new(signer);
-    let strand = builder.build_strand()
-      .subspec(subspec_string())
-      .hasher(Code::Sha3_256)
-      .details(RngStrandDetails { period: TimeDelta::seconds(60) })
-      .done()
-      .unwrap();
-
-    let pb = PayloadBuilder::new([0u8; 32].to_vec(), [1u8; 32].to_vec());
+    let (builder, strand) = builder_with_signer(signer, Code::Sha3_256, TimeDelta::seconds(60));
+    let tixels = chain(&builder, &strand, 2);
 
-    assert!(builder.build_first(strand)
-      .build_payload_then_done(pb.builder())
-      .is_err());
+    extract_randomness(&tixels[1], &tixels[0]).unwrap();
   }
 
   #[test]
   fn test_reject_late_pulse() {
-    let signer = RingSigner::generate_rs256(2048).unwrap();
-    let builder = TwineBuilder::new(signer);
-    let strand = builder.build_strand()
-      .subspec(subspec_string())
-      .hasher(Code::Sha3_256)
-      .details(RngStrandDetails { period: TimeDelta::seconds(60) })
-      .done()
-      .unwrap();
+    let (builder, strand) = builder();
 
     let pb = PayloadBuilder::new([0u8; 32].to_vec(), [1u8; 32].to_vec());
 
@@ -266,7 +807,9 @@ mod test {
     let payload = pb.builder()(&first.strand(), Some(&first)).unwrap();
     let salt = payload.salt();
     let pre = payload.pre().clone();
-    let timestamp = next_pulse_timestamp(payload.timestamp(), TimeDelta::seconds(60));
+    // pretend the next pulse was built a whole period after it was due
+    let clock = FixedClock(payload.timestamp() + TimeDelta::seconds(60));
+    let timestamp = next_pulse_timestamp_with_clock(payload.timestamp(), TimeDelta::seconds(60), &clock);
     let late_payload = RandomnessPayload::try_new(salt.into(), pre, timestamp).unwrap();
 
     let second = builder.build_next(&first)
@@ -276,6 +819,358 @@ mod test {
 
     let ret = extract_randomness(&second, &first);
     dbg!(&ret);
-    assert!(ret.is_err(), "Should reject late pulse");
+    assert!(matches!(ret, Err(RngError::TimestampOutOfPeriod)), "Should reject late pulse");
+  }
+
+  #[test]
+  fn test_reject_untrusted_period() {
+    // an irregular period that a tolerant check would accept
+    let (builder, strand) = builder_with(Code::Sha3_256, TimeDelta::seconds(97));
+    let tixels = chain(&builder, &strand, 2);
+    let (first, second) = (&tixels[0], &tixels[1]);
+
+    assert!(extract_randomness(second, first).is_ok());
+    assert!(extract_randomness_with_period(second, first, TimeDelta::seconds(97)).is_ok());
+    assert!(
+      extract_randomness_with_period(second, first, TimeDelta::seconds(60)).is_err(),
+      "Should reject a declared period that differs from the trusted period"
+    );
+  }
+
+  #[test]
+  fn test_prepare_batch() {
+    let (builder, strand) = builder();
+    let batch = PayloadBuilder::prepare_batch(&mut rand::rngs::OsRng, 3, Code::Sha3_256);
+    assert_eq!(batch.len(), 3);
+    assert!(batch.iter().all(|b| b.len() == 32));
+
+    let mut batch = batch.into_iter();
+    let pb = PayloadBuilder::new([0u8; 32].to_vec(), batch.next().unwrap());
+    let first = builder.build_first(strand)
+      .build_payload_then_done(pb.builder())
+      .unwrap();
+
+    let mut prev = first;
+    let mut pb = pb;
+    for next in batch {
+      pb = pb.advance(next);
+      let current = builder.build_next(&prev)
+        .build_payload_then_done(pb.builder())
+        .unwrap();
+      extract_randomness(&current, &prev).unwrap();
+      prev = current;
+    }
+  }
+
+  #[test]
+  fn test_reveal_transcript() {
+    use twine_protocol::twine_lib::multihash_codetable::MultihashDigest;
+    let (builder, strand) = builder();
+    let [first, second, third]: [Twine; 3] = chain(&builder, &strand, 3).try_into().unwrap();
+
+    let transcript = reveal_transcript(&[first.clone(), second.clone(), third.clone()]).unwrap();
+    assert_eq!(transcript.len(), 3);
+    assert_eq!(&transcript[0].0, first.extract_payload::<RandomnessPayload>().unwrap().pre());
+    assert!(transcript[0].1.is_empty());
+    assert_eq!(transcript[1].1, vec![1u8; 32]);
+    assert_eq!(transcript[2].1, vec![2u8; 32]);
+    assert_eq!(&transcript[2].0, &Code::Sha3_256.digest(&[3u8; 32]));
+
+    assert!(reveal_transcript(&[first, third]).is_err());
+  }
+
+  #[test]
+  fn test_compare_periods() {
+    let (_, old) = builder();
+    let (_, same) = builder();
+    let (_, faster) = builder_with(Code::Sha3_256, TimeDelta::seconds(30));
+
+    assert_eq!(compare_periods(&old, &same).unwrap(), None);
+    assert_eq!(
+      compare_periods(&old, &faster).unwrap(),
+      Some((TimeDelta::seconds(60), TimeDelta::seconds(30)))
+    );
+  }
+
+  #[test]
+  fn test_anticipate_salt() {
+    let (builder, strand) = builder();
+    let pb = PayloadBuilder::new([0u8; 32].to_vec(), [1u8; 32].to_vec());
+    let first = builder.build_first(strand)
+      .build_payload_then_done(pb.builder())
+      .unwrap();
+
+    let salt = anticipate_salt(&first, &[1u8; 32], Code::Sha3_256).unwrap();
+    assert!(matches!(anticipate_salt(&first, &[2u8; 32], Code::Sha3_256), Err(RngError::PrecommitmentMismatch)));
+    assert!(matches!(anticipate_salt(&first, &[1u8; 32], Code::Sha3_512), Err(RngError::HasherMismatch)));
+
+    let pb = pb.advance([2u8; 32].to_vec());
+    let second = builder.build_next(&first)
+      .build_payload_then_done(pb.builder())
+      .unwrap();
+    let payload = second.extract_payload::<RandomnessPayload>().unwrap();
+    assert_eq!(payload.salt(), salt);
+  }
+
+  #[test]
+  fn test_seed_stdrng_from_range() {
+    use rand::Rng;
+    let (builder, strand) = builder();
+    let range = chain(&builder, &strand, 3);
+    let a: [u8; 16] = seed_stdrng_from_range(&range).unwrap().gen();
+    let b: [u8; 16] = seed_stdrng_from_range(&range).unwrap().gen();
+    assert_eq!(a, b);
+
+    let c: [u8; 16] = seed_stdrng_from_range(&range[..2]).unwrap().gen();
+    assert_ne!(a, c);
+
+    assert!(seed_stdrng_from_range(&range[..1]).is_err());
+  }
+
+  #[test]
+  fn test_extract_randomness_chain() {
+    let (builder, strand) = builder();
+    let [first, second, third]: [Twine; 3] = chain(&builder, &strand, 3).try_into().unwrap();
+
+    let rands = extract_randomness_chain(&[first.clone(), second.clone(), third.clone()]).unwrap();
+    assert_eq!(rands, vec![
+      extract_randomness(&second, &first).unwrap(),
+      extract_randomness(&third, &second).unwrap(),
+    ]);
+
+    // genesis alone has no randomness
+    assert!(extract_randomness_chain(&[first.clone()]).unwrap().is_empty());
+
+    let err = extract_randomness_chain(&[first.clone(), second.clone(), first.clone(), third])
+      .unwrap_err();
+    match err {
+      RngError::BrokenLink { index, cid, .. } => {
+        assert_eq!(index, 2);
+        assert_eq!(cid, first.cid());
+      }
+      e => panic!("Unexpected error: {}", e),
+    }
+  }
+
+  #[cfg(feature = "rayon")]
+  fn long_chain(len: usize) -> Vec<Twine> {
+    let (builder, strand) = builder();
+    chain(&builder, &strand, len)
+  }
+
+  #[cfg(feature = "rayon")]
+  fn unverified(tixels: &[Twine]) -> Vec<Tixel> {
+    tixels.iter().map(|t| t.tixel().clone()).collect()
+  }
+
+  #[cfg(feature = "rayon")]
+  #[test]
+  fn test_extract_randomness_chain_par() {
+    let tixels = long_chain(8);
+    let strand = tixels[0].strand();
+    assert_eq!(
+      extract_randomness_chain_par(&strand, &unverified(&tixels)).unwrap(),
+      extract_randomness_chain(&tixels).unwrap()
+    );
+    assert!(extract_randomness_chain_par(&strand, &unverified(&tixels[..1])).unwrap().is_empty());
+    assert!(extract_randomness_chain_par(&strand, &[]).unwrap().is_empty());
+
+    let mut broken = tixels.clone();
+    broken.swap(3, 6);
+    let serial = extract_randomness_chain(&broken).unwrap_err();
+    let parallel = extract_randomness_chain_par(&strand, &unverified(&broken)).unwrap_err();
+    match (serial, parallel) {
+      (
+        RngError::BrokenLink { index: a, cid: cid_a, .. },
+        RngError::BrokenLink { index: b, cid: cid_b, .. },
+      ) => {
+        assert_eq!(a, 3);
+        assert_eq!(a, b);
+        assert_eq!(cid_a, cid_b);
+      }
+      e => panic!("Unexpected errors: {:?}", e),
+    }
+
+    // tixels signed by a different key don't verify against the strand
+    let (_, other) = builder();
+    match extract_randomness_chain_par(&other, &unverified(&tixels)) {
+      Err(RngError::BrokenLink { index, .. }) => assert_eq!(index, 0),
+      r => panic!("Unexpected result: {:?}", r),
+    }
+  }
+
+  #[cfg(feature = "rayon")]
+  #[test]
+  #[ignore = "slow; run with --release --features rayon -- --ignored"]
+  fn test_extract_randomness_chain_par_timing() {
+    let tixels = long_chain(4000);
+    let strand = tixels[0].strand();
+    let unverified = unverified(&tixels);
+
+    let single = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+    let start = std::time::Instant::now();
+    let serial = single.install(|| extract_randomness_chain_par(&strand, &unverified)).unwrap();
+    let serial_time = start.elapsed();
+
+    let start = std::time::Instant::now();
+    let parallel = extract_randomness_chain_par(&strand, &unverified).unwrap();
+    let parallel_time = start.elapsed();
+
+    assert_eq!(serial, extract_randomness_chain(&tixels).unwrap());
+    assert_eq!(serial, parallel);
+    if rayon::current_num_threads() > 1 {
+      assert!(
+        parallel_time < serial_time,
+        "parallel {:?} should beat serial {:?} on {} threads",
+        parallel_time,
+        serial_time,
+        rayon::current_num_threads()
+      );
+    }
+  }
+
+  #[test]
+  fn test_validate_genesis() {
+    use twine_protocol::twine_lib::multihash_codetable::MultihashDigest;
+    let (builder, strand) = builder();
+    let pb = PayloadBuilder::new([0u8; 32].to_vec(), [1u8; 32].to_vec());
+    let first = builder.build_first(strand.clone())
+      .build_payload_then_done(pb.builder())
+      .unwrap();
+    validate_genesis(&first).unwrap();
+
+    let pb = pb.advance([2u8; 32].to_vec());
+    let second = builder.build_next(&first)
+      .build_payload_then_done(pb.builder())
+      .unwrap();
+    assert!(matches!(validate_genesis(&second), Err(RngError::NotGenesis)));
+
+    let ts = chrono::DateTime::parse_from_rfc3339("2025-02-12T21:09:00Z").unwrap().to_utc();
+    let crafted = |salt: Vec<u8>, timestamp| {
+      builder.build_first(strand.clone())
+        .payload(RandomnessPayload::try_new(
+          salt.into(),
+          Code::Sha3_256.digest(&[1u8; 32]),
+          timestamp,
+        ).unwrap())
+        .done()
+        .unwrap()
+    };
+    validate_genesis(&crafted(vec![0u8; 32], ts)).unwrap();
+    let mut salt = vec![0u8; 32];
+    salt[31] = 1;
+    assert!(matches!(
+      validate_genesis(&crafted(salt, ts)),
+      Err(RngError::NonZeroGenesisSalt)
+    ));
+    assert!(matches!(
+      validate_genesis(&crafted(vec![0u8; 32], ts + TimeDelta::seconds(30))),
+      Err(RngError::TimestampOffGrid)
+    ));
+    // not a whole second, which the payload's own checks don't catch
+    assert!(matches!(
+      validate_genesis(&crafted(vec![0u8; 32], ts + TimeDelta::microseconds(1))),
+      Err(RngError::TimestampOffGrid)
+    ));
+  }
+
+  #[test]
+  fn test_min_entropy_bits() {
+    let (builder, strand) = builder();
+    let [first, second]: [Twine; 2] = chain(&builder, &strand, 2).try_into().unwrap();
+
+    assert!(assert_min_entropy_bits(&second, &first, 256).is_ok());
+    assert!(matches!(
+      assert_min_entropy_bits(&second, &first, 300),
+      Err(RngError::InsufficientEntropy { bits: 256, required: 300 })
+    ));
+  }
+
+  #[test]
+  fn test_verify_range_period() {
+    let (builder, strand) = builder();
+    let [first, second]: [Twine; 2] = chain(&builder, &strand, 2).try_into().unwrap();
+
+    // a third pulse two periods after the second
+    let pb = PayloadBuilder::new([2u8; 32].to_vec(), [3u8; 32].to_vec());
+    let payload = pb.builder()(&second.strand(), Some(&second)).unwrap();
+    let late_payload = RandomnessPayload::try_new(
+      payload.salt().to_vec().into(),
+      payload.pre().clone(),
+      payload.timestamp() + TimeDelta::seconds(60),
+    ).unwrap();
+    let third = builder.build_next(&second)
+      .payload(late_payload)
+      .done()
+      .unwrap();
+
+    assert!(verify_range_period(&[first.clone(), second.clone()], &strand).is_ok());
+    match verify_range_period(&[first, second, third], &strand).unwrap_err() {
+      RngError::BrokenLink { index, source, .. } => {
+        assert_eq!(index, 2);
+        assert!(matches!(*source, RngError::TimestampOutOfPeriod));
+      }
+      e => panic!("Unexpected error: {}", e),
+    }
+  }
+
+  #[test]
+  fn test_verify_external_commitment() {
+    use twine_protocol::twine_lib::multihash_codetable::MultihashDigest;
+    let commitment = Code::Sha3_256.digest(&[1u8; 32]);
+    assert!(verify_external_commitment(&[1u8; 32], &commitment, Code::Sha3_256).is_ok());
+    assert!(matches!(
+      verify_external_commitment(&[2u8; 32], &commitment, Code::Sha3_256),
+      Err(RngError::PrecommitmentMismatch)
+    ));
+    assert!(matches!(
+      verify_external_commitment(&[1u8; 32], &commitment, Code::Sha3_512),
+      Err(RngError::HasherMismatch)
+    ));
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_min_hash_strength() {
+    use twine_protocol::twine_lib::multihash_codetable::MultihashDigest;
+    assert_eq!(hash_strength_bits(Code::Sha3_256.into()).unwrap(), 256);
+    assert_eq!(hash_strength_bits(Code::Sha3_512.into()).unwrap(), 512);
+    assert!(hash_strength_bits(0xdeadbeef).is_err());
+
+    let ts = chrono::DateTime::parse_from_rfc3339("2025-02-12T21:10:00Z").unwrap().to_utc();
+    let weak = RandomnessPayload::try_new(
+      vec![0u8; 32].into(),
+      Code::Sha3_256.digest(&[1u8; 32]),
+      ts,
+    ).unwrap();
+    let strong = RandomnessPayload::try_new(
+      vec![0u8; 64].into(),
+      Code::Sha3_512.digest(&[1u8; 64]),
+      ts,
+    ).unwrap();
+
+    assert!(require_min_hash_strength(&weak, 256).is_ok());
+    assert!(matches!(
+      require_min_hash_strength(&weak, 384),
+      Err(RngError::WeakHash { bits: 256, min_bits: 384 })
+    ));
+    assert!(require_min_hash_strength(&strong, 384).is_ok());
+    assert!(require_min_hash_strength(&strong, 512).is_ok());
+    assert!(require_min_hash_strength(&strong, 513).is_err());
+  }
+
+  #[test]
+  fn test_random_builder() {
+    let (builder, strand) = builder();
+    let pb = PayloadBuilder::new_random(Code::Sha3_256);
+    let first = builder.build_first(strand)
+      .build_payload_then_done(pb.builder())
+      .unwrap();
+
+    let pb = pb.advance_random();
+    let second = builder.build_next(&first)
+      .build_payload_then_done(pb.builder())
+      .unwrap();
+
+    extract_randomness(&second, &first).unwrap();
+  }
+}
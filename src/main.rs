@@ -1,33 +1,204 @@
+use std::io::Write;
+use std::path::Path;
+use futures::TryStreamExt;
+use rand::RngCore;
 use twine_protocol::prelude::Resolver;
 use twine_protocol::prelude::*;
+use twine_protocol::twine_builder::RingSigner;
+use twine_protocol::twine_lib::multihash_codetable::{Code, MultihashDigest};
+use twine_spec_rng::{encode_output, extract_randomness_chain, parse_period, subspec_string, OutputEncoding, PayloadBuilder, RandomnessPayload, RngStrandDetails};
+
+fn usage(bin: &str) -> ! {
+  eprintln!("Usage: {} <url> <query|range> [--format hex|base64|base32|multibase|raw|json]", bin);
+  eprintln!("       {} create-strand <period-seconds> <sha3-256|sha3-512> <output-dir>", bin);
+  std::process::exit(1);
+}
+
+/// Write a file that only the current user can read
+fn write_secret(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+  let mut options = std::fs::OpenOptions::new();
+  options.write(true).create_new(true);
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::OpenOptionsExt;
+    options.mode(0o600);
+  }
+  options.open(path)?.write_all(contents)
+}
+
+fn create_strand(period: &str, hasher: &str, out: &str) -> Result<(), Box<dyn std::error::Error>> {
+  // validated the same way as a period read from strand details
+  let period = parse_period(&format!("PT{}S", period.parse::<u64>()?))?;
+  let hasher = match hasher {
+    "sha3-256" => Code::Sha3_256,
+    "sha3-512" => Code::Sha3_512,
+    _ => return Err(format!("Unsupported hasher: {}", hasher).into()),
+  };
+
+  // build everything in memory first, so a failure leaves nothing on disk
+  let signer = RingSigner::generate_rs256(2048)?;
+  let key_pem = signer.private_key_pem()?;
+  let builder = TwineBuilder::new(signer);
+  let strand = builder.build_strand()
+    .subspec(subspec_string())
+    .hasher(hasher)
+    .details(RngStrandDetails { period })
+    .done()?;
+
+  let mut next = vec![0u8; hasher.digest(&[]).size() as usize];
+  rand::rngs::OsRng.fill_bytes(&mut next);
+  let pb = PayloadBuilder::new(vec![], next.clone());
+  let genesis = builder.build_first(strand.clone())
+    .build_payload_then_done(pb.builder())?;
+
+  let out = Path::new(out);
+  if let Some(parent) = out.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  // fails atomically if the path exists, so an existing strand is never overwritten
+  if let Err(e) = std::fs::create_dir(out) {
+    if e.kind() == std::io::ErrorKind::AlreadyExists {
+      return Err(format!("Output path {} already exists", out.display()).into());
+    }
+    return Err(e.into());
+  }
+
+  std::fs::write(out.join("strand.json"), strand.to_string())?;
+  std::fs::write(out.join("genesis.json"), genesis.to_string())?;
+  write_secret(&out.join("key.pem"), key_pem.as_bytes())?;
+  // the value committed to by the genesis pulse, needed to build the next pulse
+  write_secret(&out.join("next.hex"), hex::encode(next).as_bytes())?;
+
+  let payload = genesis.extract_payload::<RandomnessPayload>()?;
+  println!("Strand CID: {}", strand.cid());
+  println!("Genesis CID: {}", genesis.cid());
+  println!("Genesis commitment: {}", hex::encode(payload.pre().to_bytes()));
+  Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+  Encoded(OutputEncoding),
+  Json,
+}
+
+impl std::str::FromStr for Format {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "json" => Ok(Format::Json),
+      _ => s.parse().map(Format::Encoded).map_err(|_| format!("Unknown format: {}", s)),
+    }
+  }
+}
+
+fn print_pulses(pulses: &[(Twine, Vec<u8>)], format: Format) -> Result<(), Box<dyn std::error::Error>> {
+  let mut stdout = std::io::stdout().lock();
+  match format {
+    Format::Encoded(encoding) => {
+      for (_, rand) in pulses {
+        stdout.write_all(&encode_output(rand, encoding))?;
+        if encoding.is_text() {
+          stdout.write_all(b"\n")?;
+        }
+      }
+    }
+    Format::Json => {
+      let items = pulses
+        .iter()
+        .map(|(twine, rand)| {
+          let payload = twine.extract_payload::<RandomnessPayload>()?;
+          Ok(serde_json::json!({
+            "cid": twine.cid().to_string(),
+            "timestamp": payload.timestamp(),
+            "randomness": hex::encode(rand),
+          }))
+        })
+        .collect::<Result<Vec<_>, VerificationError>>()?;
+      writeln!(stdout, "{}", serde_json::to_string_pretty(&items)?)?;
+    }
+  }
+  stdout.flush()?;
+  Ok(())
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-  // first arg: url to api
-  // second arg: twine query to rng tixel
   let args: Vec<String> = std::env::args().collect();
-  if args.len() != 3 {
-    eprintln!("Usage: {} <url> <query>", args[0]);
-    std::process::exit(1);
+  if args.get(1).map(|a| a.as_str()) == Some("create-strand") {
+    if args.len() != 5 {
+      usage(&args[0]);
+    }
+    return create_strand(&args[2], &args[3], &args[4]);
+  }
+
+  // positional args: url to api, then a twine query or range query
+  let mut format = None;
+  let mut positional = Vec::new();
+  let mut rest = args.iter().skip(1);
+  while let Some(arg) = rest.next() {
+    if arg == "--format" {
+      let Some(value) = rest.next() else { usage(&args[0]) };
+      format = Some(value.parse::<Format>()?);
+    } else {
+      positional.push(arg);
+    }
+  }
+  if positional.len() != 2 {
+    usage(&args[0]);
   }
 
-  let url = &args[1];
-  let query = &args[2];
+  let url = positional[0];
+  let query = positional[1];
 
   let client = twine_protocol::twine_http_store::reqwest::Client::new();
   let store = twine_protocol::twine_http_store::v2::HttpStore::new(client)
     .with_url(url);
 
-  let query: SingleQuery = query.parse()?;
-  let result = store.resolve(query).await?;
-  let current = result.unpack();
-  let prev = store.resolve(current.previous().unwrap()).await?;
+  if let Ok(query) = query.parse::<SingleQuery>() {
+    let result = store.resolve(query).await?;
+    let current = result.unpack();
+    let Some(link) = current.previous() else {
+      eprintln!("Pulse {} is the genesis pulse, which has no randomness", current.cid());
+      std::process::exit(1);
+    };
+    let prev = store.resolve(link).await?;
+
+    let rand = match twine_spec_rng::extract_randomness(&current, &prev) {
+      Ok(rand) => rand,
+      Err(e) => {
+        eprintln!("Pulse {} failed validation: {}", current.cid(), e);
+        std::process::exit(1);
+      }
+    };
 
-  let rand = twine_spec_rng::extract_randomness(&current, &prev)?;
+    match format {
+      None => {
+        // print as hex string
+        println!("Successfully extracted randomness:");
+        println!("{}", hex::encode(rand));
+      }
+      Some(format) => print_pulses(&[(current, rand)], format)?,
+    }
+    return Ok(());
+  }
 
-  // print as hex string
-  println!("Successfully extracted randomness:");
-  println!("{}", hex::encode(rand));
+  let range: RangeQuery = query.parse()?;
+  let mut tixels: Vec<Twine> = store.resolve_range(range).await?.try_collect().await?;
+  tixels.sort_by_key(|t| t.index());
+  // the first tixel in the range needs its previous tixel to be verified
+  if let Some(link) = tixels.first().and_then(|t| t.previous()) {
+    let prev = store.resolve(link).await?.unpack();
+    tixels.insert(0, prev);
+  }
 
-  Ok(())
-}
\ No newline at end of file
+  let rands = match extract_randomness_chain(&tixels) {
+    Ok(rands) => rands,
+    Err(e) => {
+      eprintln!("{}", e);
+      std::process::exit(1);
+    }
+  };
+  let pulses: Vec<(Twine, Vec<u8>)> = tixels.into_iter().skip(1).zip(rands).collect();
+  print_pulses(&pulses, format.unwrap_or(Format::Encoded(OutputEncoding::Hex)))
+}
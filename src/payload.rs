@@ -5,7 +5,7 @@ use twine_protocol::twine_lib::multihash_codetable::Multihash;
 use twine_protocol::twine_lib::verify::{Verifiable, Verified};
 use twine_protocol::twine_lib::Bytes;
 
-use crate::RngStrandDetails;
+use crate::{Clock, RngError, RngStrandDetails, SystemClock};
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 struct RandomnessPayloadRaw {
@@ -17,6 +17,21 @@ struct RandomnessPayloadRaw {
 impl Verifiable for RandomnessPayloadRaw {
   type Error = VerificationError;
   fn verify(&self) -> Result<(), VerificationError> {
+    // a real digest is never empty, so an empty salt would trivially match
+    if self.pre.size() == 0 {
+      return Err(VerificationError::Payload(
+        "Pre hash has a zero size digest".to_string(),
+      ));
+    }
+    // the digest must be the size the declared hash function produces
+    use twine_protocol::twine_lib::multihash_codetable::MultihashDigest;
+    let code = Code::try_from(self.pre.code())
+      .map_err(|_| VerificationError::UnsupportedHashAlgorithm)?;
+    if code.digest(&[]).size() != self.pre.size() {
+      return Err(VerificationError::Payload(
+        "Pre hash size does not match its hash function".to_string(),
+      ));
+    }
     if self.salt.len() != self.pre.size() as usize {
       return Err(VerificationError::Payload(
         "Salt length does not match pre hash size".to_string(),
@@ -50,11 +65,57 @@ impl RandomnessPayload {
     .map(Self)
   }
 
+  /// Construct a payload from its raw components
+  ///
+  /// This is intended for interop testing against other implementations,
+  /// where exact byte-level inputs are known. The precommitment is built
+  /// from `pre_code` and `pre_digest`, and the digest length must match the
+  /// canonical digest size of that code.
+  pub fn from_components(
+    salt: Vec<u8>,
+    pre_code: u64,
+    pre_digest: Vec<u8>,
+    timestamp_secs: i64,
+  ) -> Result<Self, VerificationError> {
+    let pre = Multihash::wrap(pre_code, &pre_digest)
+      .map_err(|e| VerificationError::Payload(format!("Invalid pre hash: {}", e)))?;
+    let timestamp = chrono::DateTime::from_timestamp(timestamp_secs, 0)
+      .ok_or(VerificationError::Payload("Timestamp is out of range".to_string()))?;
+    Self::try_new(Bytes(salt), pre, timestamp)
+  }
+
   pub fn new_next(
     rand: &[u8],
     pre: Multihash,
     prev: &Tixel,
     period: chrono::TimeDelta,
+  ) -> Result<Self, BuildError> {
+    Self::new_next_with_clock(rand, pre, prev, period, &SystemClock)
+  }
+
+  /// Same as [`RandomnessPayload::new_next`], but timing the pulse with `clock`
+  pub fn new_next_with_clock(
+    rand: &[u8],
+    pre: Multihash,
+    prev: &Tixel,
+    period: chrono::TimeDelta,
+    clock: &dyn Clock,
+  ) -> Result<Self, BuildError> {
+    Self::new_next_with_tolerance(rand, pre, prev, period, TimeDelta::zero(), clock)
+  }
+
+  /// Same as [`RandomnessPayload::new_next_with_clock`], but a pulse that is
+  /// late by less than `tolerance` still follows exactly one period after the
+  /// previous one
+  ///
+  /// See [`crate::next_pulse_timestamp_with_tolerance`].
+  pub fn new_next_with_tolerance(
+    rand: &[u8],
+    pre: Multihash,
+    prev: &Tixel,
+    period: chrono::TimeDelta,
+    tolerance: TimeDelta,
+    clock: &dyn Clock,
   ) -> Result<Self, BuildError> {
     // ensure rand corresponds to previous pre
     let prev_payload = prev.extract_payload::<RandomnessPayload>()?;
@@ -80,56 +141,177 @@ impl RandomnessPayload {
         .map(|(a, b)| a ^ b)
         .collect(),
     );
-    let timestamp =
-      crate::timing::next_pulse_timestamp(prev_payload.0.timestamp, period);
+    let timestamp = crate::timing::next_pulse_timestamp_with_tolerance_with_clock(
+      prev_payload.0.timestamp,
+      period,
+      tolerance,
+      clock,
+    );
     Ok(Self::try_new(salt, pre, timestamp)?)
   }
 
   pub fn new_start(
     pre: Multihash,
     period: TimeDelta,
+  ) -> Result<Self, VerificationError> {
+    Self::new_start_with_clock(pre, period, &SystemClock)
+  }
+
+  /// Same as [`RandomnessPayload::new_start`], but timing the pulse with `clock`
+  pub fn new_start_with_clock(
+    pre: Multihash,
+    period: TimeDelta,
+    clock: &dyn Clock,
   ) -> Result<Self, VerificationError> {
     let num_bytes = pre.size() as usize;
     let salt = Bytes(vec![0u8; num_bytes]);
-    let timestamp = crate::timing::next_truncated_time(period);
+    let timestamp = crate::timing::next_truncated_time_with_clock(period, clock);
     Self::try_new(salt, pre, timestamp)
   }
 
   pub fn validate_randomness(
     &self,
     prev: &Twine,
-  ) -> Result<(), VerificationError> {
+  ) -> Result<(), RngError> {
+    self.validate_randomness_inner(prev, 1, TimeDelta::zero())
+  }
+
+  /// Validate the randomness, tolerating skipped pulses
+  ///
+  /// The timestamp may be any whole number of periods after the previous
+  /// pulse, from one up to `max_gap`. The precommitment linkage is checked
+  /// as usual, since it depends on the previous CID rather than the timestamp.
+  pub fn validate_randomness_with_gap(
+    &self,
+    prev: &Twine,
+    max_gap: u32,
+  ) -> Result<(), RngError> {
+    self.validate_randomness_inner(prev, max_gap, TimeDelta::zero())
+  }
+
+  /// Validate the randomness, tolerating timestamps slightly off the grid
+  ///
+  /// The timestamp may be up to `tolerance` away from the expected grid
+  /// point, one period after the previous pulse. Anything further off is
+  /// still rejected. A zero tolerance is the same as
+  /// [`RandomnessPayload::validate_randomness`]. The tolerance should be
+  /// well under half a period to be meaningful.
+  pub fn validate_randomness_with_tolerance(
+    &self,
+    prev: &Twine,
+    tolerance: TimeDelta,
+  ) -> Result<(), RngError> {
+    self.validate_randomness_inner(prev, 1, tolerance)
+  }
+
+  fn validate_randomness_inner(
+    &self,
+    prev: &Twine,
+    max_gap: u32,
+    tolerance: TimeDelta,
+  ) -> Result<(), RngError> {
     if prev.cid().hash().size() != self.0.pre.size() {
       return Err(VerificationError::Payload(
         "Pre hash size does not match previous tixel hash size".to_string(),
-      ));
+      ).into());
     }
     let prev_payload = prev.extract_payload::<RandomnessPayload>()?;
     if self.0.timestamp < prev_payload.0.timestamp {
-      return Err(VerificationError::Payload(
-        "Timestamp is less than previous tixel timestamp".to_string(),
-      ));
+      return Err(RngError::TimestampOutOfPeriod);
     }
     // ensure it's within the period
-    let period = prev.strand().extract_details::<RngStrandDetails>()?.period;
-    if (self.0.timestamp - prev_payload.0.timestamp) != period {
-      return Err(VerificationError::Payload(
-        "Timestamps are not within one period of each other".to_string(),
-      ));
+    let details = prev.strand().extract_details::<RngStrandDetails>()?;
+    crate::timing::verify_timestamp_against_details(self.0.timestamp, &details)?;
+    let period = details.period;
+    // the gap must be within tolerance of a whole number of periods, between one and max_gap
+    let gap = self.0.timestamp - prev_payload.0.timestamp;
+    let (gap_ms, period_ms) = (gap.num_milliseconds(), period.num_milliseconds());
+    if period_ms <= 0 || TimeDelta::milliseconds(gap_ms) != gap {
+      return Err(RngError::TimestampOutOfPeriod);
     }
+    let (mut periods, remainder) = (gap_ms / period_ms, gap_ms % period_ms);
+    let deviation = if remainder * 2 >= period_ms {
+      periods += 1;
+      period_ms - remainder
+    } else {
+      remainder
+    };
+    if TimeDelta::milliseconds(deviation) > tolerance {
+      return Err(RngError::TimestampOutOfPeriod);
+    }
+    if !(1..=i64::from(max_gap)).contains(&periods) {
+      return Err(RngError::TimestampOutOfPeriod);
+    }
+    // ensure the strand hasn't drifted off the grid established at genesis
+    self.validate_grid_alignment_with_tolerance(period, tolerance)?;
 
     // check that the precommitment from the previous tixel matches the xor rand value
     let rand = self.local_random_value(prev);
+    prev_payload.verify_precommitment(&rand)
+  }
 
+  /// Verify that a revealed value matches this payload's precommitment
+  ///
+  /// This only checks the commitment itself. It does not check that the value
+  /// was correctly revealed by the next tixel (the salt XOR linkage), which
+  /// is what [`RandomnessPayload::validate_randomness`] does in addition.
+  pub fn verify_precommitment(&self, revealed: &[u8]) -> Result<(), RngError> {
+    Self::verify_commitment(self.pre(), revealed)
+  }
+
+  /// Verify that `revealed` hashes to `commitment`, with the hasher it names
+  ///
+  /// Shared with the checks of commitments published outside of a payload.
+  pub(crate) fn verify_commitment(commitment: &Multihash, revealed: &[u8]) -> Result<(), RngError> {
     use twine_protocol::twine_lib::multihash_codetable::MultihashDigest;
-    let code = Code::try_from(prev_payload.pre().code())
+    let code = Code::try_from(commitment.code())
       .map_err(|_| VerificationError::UnsupportedHashAlgorithm)?;
-    let pre = code.digest(&rand);
+    if &code.digest(revealed) != commitment {
+      return Err(RngError::PrecommitmentMismatch);
+    }
+    Ok(())
+  }
 
-    if &pre != prev_payload.pre() {
-      return Err(VerificationError::Payload(
-        "Previous tixel pre hash does not match hash of random value".to_string(),
-      ));
+  /// Validate the randomness against a period the consumer trusts
+  ///
+  /// The period declared in the strand details is chosen by the producer,
+  /// so a producer could declare whatever period happens to match an
+  /// irregular gap. This rejects any strand whose declared period differs
+  /// from the trusted one before running the usual validation.
+  pub fn validate_randomness_with_period(
+    &self,
+    prev: &Twine,
+    trusted_period: TimeDelta,
+  ) -> Result<(), RngError> {
+    let period = prev.strand().extract_details::<RngStrandDetails>()?.period;
+    if period != trusted_period {
+      return Err(RngError::PeriodMismatch { declared: period, trusted: trusted_period });
+    }
+    self.validate_randomness(prev)
+  }
+
+  /// Validate that the timestamp lies exactly on a period boundary
+  ///
+  /// Boundaries are multiples of the period since the unix epoch, as used by
+  /// [`crate::next_truncated_time`] when starting a strand. This holds for
+  /// periods that don't evenly divide a minute or hour too.
+  pub fn validate_grid_alignment(&self, period: TimeDelta) -> Result<(), RngError> {
+    self.validate_grid_alignment_with_tolerance(period, TimeDelta::zero())
+  }
+
+  /// Same as [`RandomnessPayload::validate_grid_alignment`], but accepting
+  /// timestamps up to `tolerance` away from the nearest boundary
+  pub fn validate_grid_alignment_with_tolerance(
+    &self,
+    period: TimeDelta,
+    tolerance: TimeDelta,
+  ) -> Result<(), RngError> {
+    use chrono::DurationRound;
+    let aligned = self.0.timestamp.duration_trunc(period)
+      .map_err(|e| VerificationError::Payload(format!("Unable to align timestamp: {}", e)))?;
+    let offset = self.0.timestamp - aligned;
+    if offset.min(period - offset) > tolerance {
+      return Err(RngError::TimestampOffGrid);
     }
     Ok(())
   }
@@ -159,9 +341,9 @@ impl RandomnessPayload {
 
 #[cfg(test)]
 mod test {
-  use crate::RngStrandDetails;
+  use crate::test_util::{builder_with, pulse_pair_at};
   use super::*;
-  use twine_protocol::{twine_builder::RingSigner, twine_lib::serde_ipld_dagjson};
+  use twine_protocol::twine_lib::serde_ipld_dagjson;
 
   fn valid() -> &'static str {
     r#"{
@@ -205,16 +387,7 @@ mod test {
 
   #[test]
   fn test_mallicious_data() {
-    let signer = RingSigner::generate_rs256(2048).unwrap();
-    let builder = TwineBuilder::new(signer);
-    let strand = builder.build_strand()
-      .details(RngStrandDetails {
-        period: TimeDelta::seconds(60),
-      })
-      .subspec("twine-rng/1.0.0".into())
-      .hasher(Code::Sha3_512)
-      .done()
-      .unwrap();
+    let (builder, strand) = builder_with(Code::Sha3_512, TimeDelta::seconds(60));
 
     let first = builder.build_first(strand).done().unwrap();
     use twine_protocol::twine_lib::multihash_codetable::MultihashDigest;
@@ -233,4 +406,144 @@ mod test {
     let ret = valid.validate_randomness(&second);
     assert!(ret.is_err(), "Validation should fail for malicious data {:?}", ret);
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_reject_empty_pre() {
+    let pre = Multihash::wrap(0x16, &[]).unwrap();
+    let ret = RandomnessPayload::try_new(
+      Vec::new().into(),
+      pre,
+      chrono::DateTime::parse_from_rfc3339("2025-02-12T21:10:00Z").unwrap().to_utc()
+    );
+    assert!(ret.is_err(), "Should reject an empty salt and pre");
+  }
+
+  #[test]
+  fn test_reject_inconsistent_pre() {
+    // claims to be sha3-256 but carries a 64 byte digest
+    let pre = Multihash::wrap(u64::from(Code::Sha3_256), &[7u8; 64]).unwrap();
+    let ret = RandomnessPayload::try_new(
+      [0u8; 64].to_vec().into(),
+      pre,
+      chrono::DateTime::parse_from_rfc3339("2025-02-12T21:10:00Z").unwrap().to_utc()
+    );
+    assert!(ret.is_err(), "Should reject a pre whose code and size disagree");
+  }
+
+  #[test]
+  fn test_from_components() {
+    use twine_protocol::twine_lib::multihash_codetable::MultihashDigest;
+    let expected = Code::Sha3_512.digest(&[1u8; 64]);
+    let payload = RandomnessPayload::from_components(
+      vec![3u8; 64],
+      u64::from(Code::Sha3_512),
+      expected.digest().to_vec(),
+      1739394600,
+    ).unwrap();
+    assert_eq!(payload.pre(), &expected);
+    assert_eq!(payload.salt(), &[3u8; 64]);
+    assert_eq!(payload.timestamp().to_rfc3339(), "2025-02-12T21:10:00+00:00");
+
+    // digest too short for sha3-512
+    assert!(RandomnessPayload::from_components(
+      vec![3u8; 32],
+      u64::from(Code::Sha3_512),
+      vec![1u8; 32],
+      1739394600,
+    ).is_err());
+  }
+
+  #[test]
+  fn test_verify_precommitment() {
+    use twine_protocol::twine_lib::multihash_codetable::MultihashDigest;
+    let payload = RandomnessPayload::try_new(
+      [0u8; 64].to_vec().into(),
+      Code::Sha3_512.digest(&[1u8; 64]),
+      chrono::DateTime::parse_from_rfc3339("2025-02-12T21:10:00Z").unwrap().to_utc()
+    ).unwrap();
+    assert!(payload.verify_precommitment(&[1u8; 64]).is_ok());
+    assert!(payload.verify_precommitment(&[2u8; 64]).is_err());
+    assert!(payload.verify_precommitment(&[]).is_err());
+  }
+
+  fn pulse_pair(first_ts: &str, period: TimeDelta) -> (Twine, Twine) {
+    pulse_pair_with_gap(first_ts, period, 1)
+  }
+
+  fn pulse_pair_with_gap(first_ts: &str, period: TimeDelta, gap: i32) -> (Twine, Twine) {
+    pulse_pair_with_offset(first_ts, period, period * gap)
+  }
+
+  fn pulse_pair_with_offset(first_ts: &str, period: TimeDelta, offset: TimeDelta) -> (Twine, Twine) {
+    let (builder, strand) = builder_with(Code::Sha3_256, period);
+    let first_ts = chrono::DateTime::parse_from_rfc3339(first_ts).unwrap().to_utc();
+    pulse_pair_at(&builder, strand, first_ts, offset)
+  }
+
+  #[test]
+  fn test_grid_alignment() {
+    let (first, second) = pulse_pair("2025-02-12T21:09:00Z", TimeDelta::seconds(60));
+    let payload = second.extract_payload::<RandomnessPayload>().unwrap();
+    payload.validate_randomness(&first).unwrap();
+
+    // exactly one period apart, but off the grid
+    let (first, second) = pulse_pair("2025-02-12T21:09:30Z", TimeDelta::seconds(60));
+    let payload = second.extract_payload::<RandomnessPayload>().unwrap();
+    assert!(payload.validate_randomness(&first).is_err());
+
+    // 7 seconds doesn't divide a minute, boundaries are multiples of 7s since the epoch
+    // 1739394531 = 7 * 248484933
+    let (first, second) = pulse_pair("2025-02-12T21:08:51Z", TimeDelta::seconds(7));
+    let payload = second.extract_payload::<RandomnessPayload>().unwrap();
+    payload.validate_randomness(&first).unwrap();
+    let (first, second) = pulse_pair("2025-02-12T21:09:00Z", TimeDelta::seconds(7));
+    let payload = second.extract_payload::<RandomnessPayload>().unwrap();
+    assert!(payload.validate_randomness(&first).is_err());
+  }
+
+  #[test]
+  fn test_validate_with_gap() {
+    let period = TimeDelta::seconds(60);
+
+    let (first, second) = pulse_pair_with_gap("2025-02-12T21:09:00Z", period, 1);
+    let payload = second.extract_payload::<RandomnessPayload>().unwrap();
+    payload.validate_randomness(&first).unwrap();
+    payload.validate_randomness_with_gap(&first, 3).unwrap();
+
+    let (first, second) = pulse_pair_with_gap("2025-02-12T21:09:00Z", period, 2);
+    let payload = second.extract_payload::<RandomnessPayload>().unwrap();
+    assert!(payload.validate_randomness(&first).is_err());
+    payload.validate_randomness_with_gap(&first, 3).unwrap();
+
+    let (first, second) = pulse_pair_with_gap("2025-02-12T21:09:00Z", period, 4);
+    let payload = second.extract_payload::<RandomnessPayload>().unwrap();
+    assert!(matches!(
+      payload.validate_randomness_with_gap(&first, 3),
+      Err(RngError::TimestampOutOfPeriod)
+    ));
+  }
+
+  #[test]
+  fn test_validate_with_tolerance() {
+    let period = TimeDelta::seconds(60);
+    let tolerance = TimeDelta::seconds(2);
+    let validate = |offset: i64, tolerance: TimeDelta| {
+      let (first, second) = pulse_pair_with_offset("2025-02-12T21:09:00Z", period, TimeDelta::seconds(offset));
+      let payload = second.extract_payload::<RandomnessPayload>().unwrap();
+      payload.validate_randomness_with_tolerance(&first, tolerance)
+    };
+
+    validate(60, TimeDelta::zero()).unwrap();
+    validate(60, tolerance).unwrap();
+    // zero tolerance is the default behaviour
+    assert!(validate(61, TimeDelta::zero()).is_err());
+    // just inside
+    validate(62, tolerance).unwrap();
+    validate(58, tolerance).unwrap();
+    // just outside
+    assert!(matches!(validate(63, tolerance), Err(RngError::TimestampOutOfPeriod)));
+    assert!(matches!(validate(57, tolerance), Err(RngError::TimestampOutOfPeriod)));
+    // still exactly one period
+    assert!(validate(121, tolerance).is_err());
+  }
+}
@@ -0,0 +1,155 @@
+//! (De)serialization for the strand period
+//!
+//! Periods are still written in the [`TimeDelta`] representation that
+//! twine-rng/1.0.0 strands use, so other 1.0.0 implementations can read
+//! them. When reading, an ISO-8601 duration such as `"PT1M"` is also
+//! accepted (see [`parse_period`]); [`format_period`] produces one.
+use chrono::TimeDelta;
+use serde::{de::Error as _, ser::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+fn check_period(period: TimeDelta) -> Result<TimeDelta, String> {
+  if period <= TimeDelta::zero() {
+    return Err(format!("Period must be positive, got {}", period));
+  }
+  if period.subsec_nanos() != 0 {
+    return Err(format!("Period must be a whole number of seconds, got {}", period));
+  }
+  Ok(period)
+}
+
+/// Format a strand period as an ISO-8601 duration
+///
+/// Fails for periods that aren't a positive whole number of seconds.
+pub fn format_period(period: TimeDelta) -> Result<String, String> {
+  let secs = check_period(period)?.num_seconds();
+  let mut out = "PT".to_string();
+  for (value, unit) in [(secs / 3600, 'H'), (secs / 60 % 60, 'M'), (secs % 60, 'S')] {
+    if value > 0 {
+      out.push_str(&format!("{}{}", value, unit));
+    }
+  }
+  Ok(out)
+}
+
+/// Parse a strand period from an ISO-8601 duration
+///
+/// Years and months are rejected since they don't have a fixed length, and
+/// so are fractional components, zero and negative durations.
+pub fn parse_period(s: &str) -> Result<TimeDelta, String> {
+  let invalid = || format!("Invalid ISO-8601 duration: {}", s);
+  if s.starts_with('-') {
+    return Err(format!("Period must be positive, got {}", s));
+  }
+  let rest = s.strip_prefix('P').ok_or_else(invalid)?;
+  let (date, time) = match rest.split_once('T') {
+    Some((_, "")) => return Err(invalid()),
+    Some((date, time)) => (date, time),
+    None => (rest, ""),
+  };
+  if date.is_empty() && time.is_empty() {
+    return Err(invalid());
+  }
+  let mut total: i64 = 0;
+  for (mut part, units) in [
+    (date, &[('W', 604800), ('D', 86400)][..]),
+    (time, &[('H', 3600), ('M', 60), ('S', 1)][..]),
+  ] {
+    let mut next_unit = 0;
+    while !part.is_empty() {
+      let end = part.find(|c: char| c.is_ascii_alphabetic()).ok_or_else(invalid)?;
+      let unit = part[end..].chars().next().unwrap();
+      let index = units[next_unit..].iter().position(|(u, _)| *u == unit).ok_or_else(invalid)? + next_unit;
+      next_unit = index + 1;
+      let number = &part[..end];
+      if number.contains(['.', ',']) {
+        return Err(format!("Period must be a whole number of seconds, got {}", s));
+      }
+      if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+      }
+      total = number.parse::<i64>().ok()
+        .and_then(|n| n.checked_mul(units[index].1))
+        .and_then(|n| n.checked_add(total))
+        .ok_or_else(invalid)?;
+      part = &part[end + 1..];
+    }
+  }
+  check_period(TimeDelta::try_seconds(total).ok_or_else(invalid)?)
+}
+
+pub(crate) fn serialize<S: Serializer>(period: &TimeDelta, serializer: S) -> Result<S::Ok, S::Error> {
+  check_period(*period).map_err(S::Error::custom)?.serialize(serializer)
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<TimeDelta, D::Error> {
+  #[derive(Deserialize)]
+  #[serde(untagged)]
+  enum PeriodRepr {
+    Iso(String),
+    Legacy(TimeDelta),
+  }
+  match PeriodRepr::deserialize(deserializer)? {
+    PeriodRepr::Iso(s) => parse_period(&s),
+    PeriodRepr::Legacy(period) => check_period(period),
+  }
+  .map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod test {
+  use chrono::TimeDelta;
+  use crate::*;
+
+  #[test]
+  fn test_round_trip() {
+    for (period, iso) in [
+      (TimeDelta::seconds(60), "PT1M"),
+      (TimeDelta::seconds(45), "PT45S"),
+      (TimeDelta::minutes(5), "PT5M"),
+      (TimeDelta::seconds(3661), "PT1H1M1S"),
+      (TimeDelta::days(1), "PT24H"),
+    ] {
+      assert_eq!(format_period(period).unwrap(), iso);
+      // written in the 1.0.0 form
+      let json = serde_json::to_value(RngStrandDetails { period }).unwrap();
+      assert_eq!(json, serde_json::json!({ "period": serde_json::to_value(period).unwrap() }));
+      let details: RngStrandDetails = serde_json::from_value(json).unwrap();
+      assert_eq!(details.period, period);
+      // ISO is accepted on read
+      let details: RngStrandDetails = serde_json::from_value(serde_json::json!({ "period": iso })).unwrap();
+      assert_eq!(details.period, period);
+    }
+  }
+
+  #[test]
+  fn test_parse_period() {
+    assert_eq!(parse_period("PT60S").unwrap(), TimeDelta::seconds(60));
+    assert_eq!(parse_period("PT5M").unwrap(), TimeDelta::minutes(5));
+    assert_eq!(parse_period("P1DT1S").unwrap(), TimeDelta::seconds(86401));
+    assert_eq!(parse_period("P1W").unwrap(), TimeDelta::weeks(1));
+    assert_eq!(parse_period("PT1.0S").unwrap_err(), "Period must be a whole number of seconds, got PT1.0S");
+    for bad in ["", "60", "P", "PT", "PT0S", "-PT60S", "P1Y", "PT5S1M", "PTS", "P1H"] {
+      assert!(parse_period(bad).is_err(), "{} should not parse", bad);
+    }
+  }
+
+  #[test]
+  fn test_reject_bad_periods() {
+    let result = serde_json::from_value::<RngStrandDetails>(serde_json::json!({ "period": "PT0.5S" }));
+    assert!(result.is_err());
+    let result = serde_json::from_value::<RngStrandDetails>(serde_json::json!({ "period": "PT0S" }));
+    assert!(result.is_err());
+    assert!(serde_json::to_value(RngStrandDetails { period: TimeDelta::milliseconds(500) }).is_err());
+    assert!(serde_json::to_value(RngStrandDetails { period: TimeDelta::seconds(-60) }).is_err());
+  }
+
+  #[test]
+  fn test_legacy_period() {
+    let legacy = serde_json::json!({ "period": serde_json::to_value(TimeDelta::seconds(60)).unwrap() });
+    let details: RngStrandDetails = serde_json::from_value(legacy).unwrap();
+    assert_eq!(details.period, TimeDelta::seconds(60));
+
+    let legacy = serde_json::json!({ "period": serde_json::to_value(TimeDelta::milliseconds(500)).unwrap() });
+    assert!(serde_json::from_value::<RngStrandDetails>(legacy).is_err());
+  }
+}
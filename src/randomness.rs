@@ -0,0 +1,109 @@
+use std::fmt::Display;
+use chrono::{DateTime, Utc};
+use twine_protocol::prelude::*;
+use twine_protocol::twine_lib::Cid;
+
+use crate::{expand_randomness, RngError};
+
+/// Verified randomness along with the pulse it came from
+///
+/// Returned by [`crate::extract_randomness_typed`] and the other `_typed`
+/// extraction functions. Displays as hex.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Randomness {
+  bytes: Vec<u8>,
+  tixel_cid: Cid,
+  strand_cid: Cid,
+  timestamp: DateTime<Utc>,
+}
+
+impl Randomness {
+  pub(crate) fn new(bytes: Vec<u8>, tixel_cid: Cid, strand_cid: Cid, timestamp: DateTime<Utc>) -> Self {
+    Self { bytes, tixel_cid, strand_cid, timestamp }
+  }
+
+  /// The random bytes
+  pub fn bytes(&self) -> &[u8] {
+    &self.bytes
+  }
+
+  /// The random bytes, discarding the provenance
+  pub fn into_bytes(self) -> Vec<u8> {
+    self.bytes
+  }
+
+  /// The CID of the tixel the randomness was extracted from
+  pub fn tixel_cid(&self) -> &Cid {
+    &self.tixel_cid
+  }
+
+  /// The CID of the strand the tixel belongs to
+  pub fn strand_cid(&self) -> &Cid {
+    &self.strand_cid
+  }
+
+  /// The timestamp of the pulse
+  pub fn timestamp(&self) -> DateTime<Utc> {
+    self.timestamp
+  }
+
+  /// The random bytes as lowercase hex
+  pub fn to_hex(&self) -> String {
+    hex::encode(&self.bytes)
+  }
+
+  /// Expand the randomness with HKDF
+  ///
+  /// See [`expand_randomness`].
+  pub fn expand(&self, info: &[u8], out_len: usize) -> Result<Vec<u8>, RngError> {
+    expand_randomness(&self.bytes, info, out_len)
+  }
+}
+
+impl AsRef<[u8]> for Randomness {
+  fn as_ref(&self) -> &[u8] {
+    &self.bytes
+  }
+}
+
+impl From<Randomness> for Vec<u8> {
+  fn from(r: Randomness) -> Self {
+    r.bytes
+  }
+}
+
+impl Display for Randomness {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.to_hex())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::test_util::{builder, chain};
+  use crate::*;
+
+  #[test]
+  fn test_typed_randomness() {
+    let (builder, strand) = builder();
+    let tixels = chain(&builder, &strand, 2);
+    let (first, second) = (&tixels[0], &tixels[1]);
+
+    let rand = extract_randomness_typed(second, first).unwrap();
+    assert_eq!(rand.tixel_cid(), &second.cid());
+    assert_eq!(rand.strand_cid(), &strand.cid());
+    assert_eq!(rand.timestamp(), second.extract_payload::<RandomnessPayload>().unwrap().timestamp());
+    assert_eq!(rand.bytes(), extract_randomness(second, first).unwrap());
+    assert_eq!(rand.to_string(), rand.to_hex());
+    assert_eq!(rand.to_hex().len(), 64);
+    assert_eq!(rand.expand(b"test", 64).unwrap(), expand_randomness(rand.bytes(), b"test", 64).unwrap());
+
+    assert!(extract_randomness_typed(first, second).is_err());
+
+    let period = chrono::TimeDelta::seconds(60);
+    assert_eq!(extract_randomness_typed_with_period(second, first, period).unwrap(), rand);
+    assert_eq!(extract_randomness_typed_with_gap(second, first, 2).unwrap(), rand);
+    assert_eq!(extract_randomness_typed_with_tolerance(second, first, chrono::TimeDelta::zero()).unwrap(), rand);
+    assert_eq!(extract_randomness_chain_typed(&tixels).unwrap(), vec![rand]);
+  }
+}
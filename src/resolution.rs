@@ -0,0 +1,357 @@
+use chrono::{DateTime, Utc};
+use twine_protocol::prelude::*;
+use twine_protocol::twine_lib::Cid;
+use twine_protocol::twine_lib::multihash_codetable::Multihash;
+
+use crate::{extract_randomness, extract_randomness_chain_with_gap, timestamp_of_round, RandomnessPayload, RngError, RngStrandDetails};
+
+/// The maximum number of tixels returned by [`pulses_since`]
+pub const MAX_PULSES_SINCE: u64 = 1000;
+
+/// The gap allowed between pulses when verifying by round
+///
+/// Rounds without a pulse are skipped, so a pulse may follow its previous
+/// pulse by any whole number of periods.
+pub(crate) const MAX_ROUND_GAP: u32 = u32::MAX;
+
+pub(crate) fn resolution_error<E: std::fmt::Display>(e: E) -> RngError {
+  RngError::Resolution(e.to_string())
+}
+
+/// Verify a tixel by walking back to an anchor tixel that is already trusted
+///
+/// Every pair between `target` and `anchor` is verified with [`extract_randomness`].
+/// The walk is bounded by `max_rounds` so that a distant (or bogus) anchor can't
+/// cause an unbounded number of resolutions. Returns the randomness of `target`.
+pub async fn verify_from_anchor<R: Resolver>(
+  resolver: &R,
+  target: &Twine,
+  anchor: &Twine,
+  max_rounds: u64,
+) -> Result<Vec<u8>, RngError> {
+  if target.strand_cid() != anchor.strand_cid() {
+    return Err(RngError::StrandMismatch);
+  }
+  // the target must come after the anchor
+  if target.index() <= anchor.index() {
+    return Err(RngError::AnchorMismatch);
+  }
+  if target.index() - anchor.index() > max_rounds {
+    return Err(RngError::AnchorTooFar { max_rounds });
+  }
+
+  let mut output = None;
+  let mut current = target.clone();
+  while current.index() > anchor.index() {
+    let link = current.previous().ok_or(RngError::NoPreviousLink)?;
+    let prev = resolver.resolve(link).await.map_err(resolution_error)?.unpack();
+    let rand = extract_randomness(&current, &prev)?;
+    output.get_or_insert(rand);
+    current = prev;
+  }
+
+  if current.cid() != anchor.cid() {
+    return Err(RngError::AnchorMismatch);
+  }
+  // the target is after the anchor, so the loop ran at least once
+  output.ok_or(RngError::AnchorMismatch)
+}
+
+/// Extract the randomness from a twine pair, anchored to a trusted genesis commitment
+///
+/// Rather than trusting the strand CID, this walks the strand back to its
+/// genesis tixel, verifying every pair on the way, and checks that the
+/// genesis precommitment matches `trusted_genesis_pre`. This requires
+/// resolving every tixel before `prev`, so the cost grows linearly with
+/// the age of the strand.
+pub async fn extract_randomness_anchored<R: Resolver>(
+  current: &Twine,
+  prev: &Twine,
+  trusted_genesis_pre: &Multihash,
+  resolver: &R,
+) -> Result<Vec<u8>, RngError> {
+  let rand = extract_randomness(current, prev)?;
+  let mut tixel = prev.clone();
+  while let Some(link) = tixel.previous() {
+    let before = resolver.resolve(link).await.map_err(resolution_error)?.unpack();
+    extract_randomness(&tixel, &before)?;
+    tixel = before;
+  }
+  let genesis_payload = tixel.extract_payload::<RandomnessPayload>()?;
+  if genesis_payload.pre() != trusted_genesis_pre {
+    return Err(RngError::GenesisCommitmentMismatch);
+  }
+  Ok(rand)
+}
+
+fn payload_timestamp(twine: &Twine) -> Result<DateTime<Utc>, RngError> {
+  Ok(twine.extract_payload::<RandomnessPayload>()?.timestamp())
+}
+
+/// Resolve the tixel whose pulse covers the given instant
+///
+/// This is the latest tixel with a timestamp at or before `timestamp`.
+/// Since tixels can be resolved by index, the strand is binary searched
+/// by timestamp, so only a logarithmic number of tixels are resolved.
+pub async fn resolve_at_timestamp<R: Resolver>(
+  resolver: &R,
+  strand_cid: &Cid,
+  timestamp: DateTime<Utc>,
+) -> Result<Twine, RngError> {
+  let latest = resolver.resolve_latest(strand_cid).await.map_err(resolution_error)?.unpack();
+  if payload_timestamp(&latest)? <= timestamp {
+    return Ok(latest);
+  }
+  let genesis = resolver.resolve_index(strand_cid, 0).await.map_err(resolution_error)?.unpack();
+  search_at_timestamp(resolver, strand_cid, timestamp, genesis, latest).await
+}
+
+/// Same as [`resolve_at_timestamp`], with the genesis and latest tixels
+/// already resolved
+pub(crate) async fn search_at_timestamp<R: Resolver>(
+  resolver: &R,
+  strand_cid: &Cid,
+  timestamp: DateTime<Utc>,
+  genesis: Twine,
+  latest: Twine,
+) -> Result<Twine, RngError> {
+  if payload_timestamp(&latest)? <= timestamp {
+    return Ok(latest);
+  }
+  if payload_timestamp(&genesis)? > timestamp {
+    return Err(RngError::BeforeGenesis);
+  }
+  // invariant: tixel at `lo` is at or before timestamp, tixel at `hi` is after
+  let (mut lo, mut hi) = (genesis, latest);
+  while hi.index() - lo.index() > 1 {
+    let mid = lo.index() + (hi.index() - lo.index()) / 2;
+    let tixel = resolver.resolve_index(strand_cid, mid).await.map_err(resolution_error)?.unpack();
+    if payload_timestamp(&tixel)? <= timestamp {
+      lo = tixel;
+    } else {
+      hi = tixel;
+    }
+  }
+  Ok(lo)
+}
+
+/// Resolve the tixel for a given round of the beacon
+///
+/// Rounds are counted in periods since the genesis pulse, so round `n` is the
+/// pulse with timestamp `genesis + n * period`. This differs from the tixel
+/// index if the strand has skipped pulses. Rounds after the latest pulse
+/// (including rounds too far in the future to have a timestamp) are
+/// [`RngError::NotYetPublished`], and earlier rounds without a pulse are
+/// [`RngError::MissingRound`].
+pub async fn resolve_round<R: Resolver>(
+  resolver: &R,
+  strand_cid: &Cid,
+  round: u64,
+) -> Result<Twine, RngError> {
+  let genesis = resolver.resolve_index(strand_cid, 0).await.map_err(resolution_error)?.unpack();
+  let period = genesis.strand().extract_details::<RngStrandDetails>()?.period;
+  let target = timestamp_of_round(payload_timestamp(&genesis)?, period, round)
+    .ok_or(RngError::NotYetPublished)?;
+  let latest = resolver.resolve_latest(strand_cid).await.map_err(resolution_error)?.unpack();
+  if target > payload_timestamp(&latest)? {
+    return Err(RngError::NotYetPublished);
+  }
+  let tixel = search_at_timestamp(resolver, strand_cid, target, genesis, latest).await?;
+  if payload_timestamp(&tixel)? != target {
+    return Err(RngError::MissingRound(round));
+  }
+  Ok(tixel)
+}
+
+/// Resolve and verify the randomness of the latest `k` pulses on a strand
+///
+/// Results are ordered newest first as `(index, randomness)` pairs. Tixels
+/// are resolved one at a time by walking back from the latest, so at most
+/// `k + 1` resolutions are made. If the strand has fewer than `k` pulses
+/// with randomness, all of them are returned.
+pub async fn recent_outputs<R: Resolver>(
+  resolver: &R,
+  strand_cid: &Cid,
+  k: usize,
+) -> Result<Vec<(u64, Vec<u8>)>, RngError> {
+  let mut outputs = Vec::with_capacity(k);
+  if k == 0 {
+    return Ok(outputs);
+  }
+  let mut current = resolver.resolve_latest(strand_cid).await.map_err(resolution_error)?.unpack();
+  while outputs.len() < k {
+    let Some(link) = current.previous() else {
+      break;
+    };
+    let prev = resolver.resolve(link).await.map_err(resolution_error)?.unpack();
+    let rand = extract_randomness(&current, &prev)?;
+    outputs.push((current.index(), rand));
+    current = prev;
+  }
+  Ok(outputs)
+}
+
+/// Resolve the tixels published after a given round, for syncing clients
+///
+/// Returns the tixels after the pulse for `since_round` (see
+/// [`resolve_round`]), oldest first, with every pair (including the link to
+/// the pulse for `since_round`) verified. Skipped rounds between pulses are
+/// allowed, as in [`extract_randomness_chain_with_gap`]. A round that hasn't
+/// been published yet has nothing after it. At most [`MAX_PULSES_SINCE`]
+/// tixels are returned; a client that is further behind should call again
+/// with the round of the last tixel it received (see [`crate::round_of`]).
+/// Tixels are resolved one at a time.
+pub async fn pulses_since<R: Resolver>(
+  resolver: &R,
+  strand_cid: &Cid,
+  since_round: u64,
+) -> Result<Vec<Twine>, RngError> {
+  let since_index = match resolve_round(resolver, strand_cid, since_round).await {
+    Ok(tixel) => tixel.index(),
+    Err(RngError::NotYetPublished) => return Ok(Vec::new()),
+    Err(e) => return Err(e),
+  };
+  let latest = resolver.resolve_latest(strand_cid).await.map_err(resolution_error)?.unpack();
+  if latest.index() <= since_index {
+    return Ok(Vec::new());
+  }
+  let end = std::cmp::min(latest.index(), since_index.saturating_add(MAX_PULSES_SINCE));
+  let mut current = if end == latest.index() {
+    latest
+  } else {
+    resolver.resolve_index(strand_cid, end).await.map_err(resolution_error)?.unpack()
+  };
+  let mut tixels = vec![current.clone()];
+  while current.index() > since_index {
+    let link = current.previous().ok_or(RngError::NoPreviousLink)?;
+    current = resolver.resolve(link).await.map_err(resolution_error)?.unpack();
+    tixels.push(current.clone());
+  }
+  tixels.reverse();
+  extract_randomness_chain_with_gap(&tixels, MAX_ROUND_GAP)?;
+  // the pulse for since_round is already known to the client
+  tixels.remove(0);
+  Ok(tixels)
+}
+
+#[cfg(test)]
+mod test {
+  use futures::executor::block_on;
+  use twine_protocol::twine_lib::store::MemoryStore;
+  use chrono::TimeDelta;
+  use crate::test_util::{builder, pulse_pair_at, store};
+  use crate::*;
+  use super::*;
+
+  fn chain(len: usize) -> (MemoryStore, Vec<Twine>) {
+    let (builder, strand) = builder();
+    let tixels = crate::test_util::chain(&builder, &strand, len);
+    (store(&strand, &tixels), tixels)
+  }
+
+  #[test]
+  fn test_verify_from_anchor() {
+    let (store, tixels) = chain(5);
+    let rand = block_on(verify_from_anchor(&store, &tixels[4], &tixels[1], 10)).unwrap();
+    assert_eq!(rand, extract_randomness(&tixels[4], &tixels[3]).unwrap());
+
+    // too far back
+    assert!(matches!(
+      block_on(verify_from_anchor(&store, &tixels[4], &tixels[0], 3)),
+      Err(RngError::AnchorTooFar { max_rounds: 3 })
+    ));
+    // anchor after target
+    assert!(matches!(
+      block_on(verify_from_anchor(&store, &tixels[1], &tixels[4], 10)),
+      Err(RngError::AnchorMismatch)
+    ));
+  }
+
+  #[test]
+  fn test_resolve_at_timestamp() {
+    let (store, tixels) = chain(6);
+    let strand_cid = tixels[0].strand_cid();
+    let ts = |t: &Twine| t.extract_payload::<RandomnessPayload>().unwrap().timestamp();
+
+    for t in &tixels {
+      let found = block_on(resolve_at_timestamp(&store, &strand_cid, ts(t))).unwrap();
+      assert_eq!(found.cid(), t.cid());
+      // part way through the period is still covered by the same pulse
+      let found = block_on(resolve_at_timestamp(&store, &strand_cid, ts(t) + TimeDelta::seconds(30))).unwrap();
+      assert_eq!(found.cid(), t.cid());
+    }
+
+    assert!(matches!(
+      block_on(resolve_at_timestamp(&store, &strand_cid, ts(&tixels[0]) - TimeDelta::seconds(1))),
+      Err(RngError::BeforeGenesis)
+    ));
+
+    let found = block_on(resolve_round(&store, &strand_cid, 3)).unwrap();
+    assert_eq!(found.cid(), tixels[3].cid());
+    assert!(matches!(
+      block_on(resolve_round(&store, &strand_cid, 100)),
+      Err(RngError::NotYetPublished)
+    ));
+    assert!(matches!(
+      block_on(resolve_round(&store, &strand_cid, u64::MAX)),
+      Err(RngError::NotYetPublished)
+    ));
+  }
+
+  #[test]
+  fn test_recent_outputs() {
+    let (store, tixels) = chain(4);
+    let strand_cid = tixels[0].strand_cid();
+
+    let recent = block_on(recent_outputs(&store, &strand_cid, 2)).unwrap();
+    assert_eq!(recent.len(), 2);
+    assert_eq!(recent[0], (3, extract_randomness(&tixels[3], &tixels[2]).unwrap()));
+    assert_eq!(recent[1], (2, extract_randomness(&tixels[2], &tixels[1]).unwrap()));
+
+    // shorter than requested
+    let recent = block_on(recent_outputs(&store, &strand_cid, 10)).unwrap();
+    assert_eq!(recent.len(), 3);
+  }
+
+  #[test]
+  fn test_extract_randomness_anchored() {
+    let (store, tixels) = chain(4);
+    let genesis_pre = tixels[0].extract_payload::<RandomnessPayload>().unwrap().pre().clone();
+    let rand = block_on(extract_randomness_anchored(&tixels[3], &tixels[2], &genesis_pre, &store)).unwrap();
+    assert_eq!(rand, extract_randomness(&tixels[3], &tixels[2]).unwrap());
+
+    let other_pre = tixels[1].extract_payload::<RandomnessPayload>().unwrap().pre().clone();
+    assert!(matches!(
+      block_on(extract_randomness_anchored(&tixels[3], &tixels[2], &other_pre, &store)),
+      Err(RngError::GenesisCommitmentMismatch)
+    ));
+  }
+
+  #[test]
+  fn test_pulses_since() {
+    let (store, tixels) = chain(5);
+    let strand_cid = tixels[0].strand_cid();
+
+    let since = block_on(pulses_since(&store, &strand_cid, 1)).unwrap();
+    let cids: Vec<_> = since.iter().map(|t| t.cid()).collect();
+    assert_eq!(cids, vec![tixels[2].cid(), tixels[3].cid(), tixels[4].cid()]);
+
+    assert!(block_on(pulses_since(&store, &strand_cid, 4)).unwrap().is_empty());
+    assert!(block_on(pulses_since(&store, &strand_cid, 10)).unwrap().is_empty());
+
+    // rounds are counted in periods, so a skipped round has no pulse
+    let (builder, strand) = builder();
+    let ts = chrono::DateTime::parse_from_rfc3339("2025-02-12T21:11:00Z").unwrap().to_utc();
+    let (first, second) = pulse_pair_at(&builder, strand.clone(), ts, TimeDelta::seconds(120));
+    let store = crate::test_util::store(&strand, &[first, second.clone()]);
+    // syncing from genesis crosses the skipped round
+    let since = block_on(pulses_since(&store, &strand.cid(), 0)).unwrap();
+    let cids: Vec<_> = since.iter().map(|t| t.cid()).collect();
+    assert_eq!(cids, vec![second.cid()]);
+    assert!(matches!(
+      block_on(pulses_since(&store, &strand.cid(), 1)),
+      Err(RngError::MissingRound(1))
+    ));
+    assert!(block_on(pulses_since(&store, &strand.cid(), 2)).unwrap().is_empty());
+  }
+}
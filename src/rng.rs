@@ -0,0 +1,178 @@
+use chrono::TimeDelta;
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use twine_protocol::prelude::*;
+use twine_protocol::twine_lib::multihash_codetable::{Code, MultihashDigest};
+
+use crate::{extract_randomness, RngError};
+
+/// A deterministic rng seeded from beacon randomness
+///
+/// The randomness seeds a ChaCha20 stream, so everyone who extracts the
+/// same pulse gets an identical byte stream.
+///
+/// The seed is 32 bytes. Randomness of exactly 32 bytes (e.g. from a
+/// Sha3_256 strand) is used as the seed directly. Randomness of any other
+/// length (e.g. 64 bytes from a Sha3_512 strand) is hashed with SHA3-256
+/// to produce the seed, so that no part of it is discarded.
+///
+/// # Example
+///
+/// ```
+/// use rand::Rng;
+/// use twine_spec_rng::TwineRng;
+/// let mut rng = TwineRng::from_randomness(&[7u8; 32]);
+/// let roll: u8 = rng.gen_range(1..=6);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TwineRng(ChaCha20Rng);
+
+impl TwineRng {
+  /// Create an rng from randomness returned by [`extract_randomness`]
+  pub fn from_randomness(randomness: &[u8]) -> Self {
+    let seed: [u8; 32] = match randomness.try_into() {
+      Ok(seed) => seed,
+      Err(_) => Code::Sha3_256
+        .digest(randomness)
+        .digest()
+        .try_into()
+        .expect("Sha3_256 digest is 32 bytes"),
+    };
+    Self::from_seed(seed)
+  }
+
+  /// Create an rng from the randomness of a twine pair
+  ///
+  /// The pair is validated with [`extract_randomness`].
+  pub fn from_twine(current: &Twine, prev: &Twine) -> Result<Self, RngError> {
+    let randomness = extract_randomness(current, prev)?;
+    Ok(Self::from_randomness(&randomness))
+  }
+}
+
+impl SeedableRng for TwineRng {
+  type Seed = [u8; 32];
+
+  fn from_seed(seed: Self::Seed) -> Self {
+    Self(ChaCha20Rng::from_seed(seed))
+  }
+}
+
+impl RngCore for TwineRng {
+  fn next_u32(&mut self) -> u32 {
+    self.0.next_u32()
+  }
+
+  fn next_u64(&mut self) -> u64 {
+    self.0.next_u64()
+  }
+
+  fn fill_bytes(&mut self, dest: &mut [u8]) {
+    self.0.fill_bytes(dest)
+  }
+
+  fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+    self.0.try_fill_bytes(dest)
+  }
+}
+
+impl CryptoRng for TwineRng {}
+
+/// Derive a deterministic delay in `[0, max)` from a pulse
+///
+/// The delay is drawn uniformly with nanosecond resolution from a
+/// [`TwineRng`] seeded by the pulse, so anyone with the same pair of tixels
+/// computes the same delay. Rejection sampling is done here on the raw
+/// ChaCha output, rather than with `rand`'s range sampling, whose algorithm
+/// may change between versions of `rand`. Workers wanting different
+/// delays from the same pulse should use [`TwineRng`] directly, e.g. by
+/// drawing once per worker in an agreed order.
+pub fn derive_jitter(
+  current: &Twine,
+  prev: &Twine,
+  max: TimeDelta,
+) -> Result<TimeDelta, RngError> {
+  let max_nanos = max.num_nanoseconds().ok_or(VerificationError::General(
+    "Maximum jitter is too large".to_string(),
+  ))?;
+  if max_nanos <= 0 {
+    return Err(VerificationError::General(
+      "Maximum jitter must be positive".to_string(),
+    ).into());
+  }
+  let mut rng = TwineRng::from_twine(current, prev)?;
+  let range = max_nanos as u64;
+  // reject draws from the incomplete block at the top, to avoid modulo bias
+  let zone = (u64::MAX / range) * range;
+  loop {
+    let draw = rng.next_u64();
+    if draw < zone {
+      return Ok(TimeDelta::nanoseconds((draw % range) as i64));
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use chrono::TimeDelta;
+  use crate::test_util::{builder, chain};
+  use crate::*;
+  use super::*;
+
+  fn sample(rng: &mut TwineRng) -> [u8; 64] {
+    let mut out = [0u8; 64];
+    rng.fill_bytes(&mut out);
+    out
+  }
+
+  #[test]
+  fn test_reproducible() {
+    let (builder, strand) = builder();
+    let tixels = chain(&builder, &strand, 3);
+    let (first, second, third) = (&tixels[0], &tixels[1], &tixels[2]);
+
+    let a = sample(&mut TwineRng::from_twine(second, first).unwrap());
+    let b = sample(&mut TwineRng::from_twine(second, first).unwrap());
+    assert_eq!(a, b);
+
+    let rand = extract_randomness(second, first).unwrap();
+    assert_eq!(a, sample(&mut TwineRng::from_randomness(&rand)));
+
+    let c = sample(&mut TwineRng::from_twine(third, second).unwrap());
+    assert_ne!(a, c);
+
+    assert!(TwineRng::from_twine(first, second).is_err());
+  }
+
+  #[test]
+  fn test_fold_long_randomness() {
+    let long = [5u8; 64];
+    let a = sample(&mut TwineRng::from_randomness(&long));
+    let b = sample(&mut TwineRng::from_randomness(&long));
+    assert_eq!(a, b);
+
+    // differing only in the second half must still change the stream
+    let mut other = long;
+    other[63] = 6;
+    assert_ne!(a, sample(&mut TwineRng::from_randomness(&other)));
+  }
+
+  #[test]
+  fn test_derive_jitter() {
+    let (builder, strand) = builder();
+    let tixels = chain(&builder, &strand, 2);
+    let (first, second) = (&tixels[0], &tixels[1]);
+
+    let max = TimeDelta::minutes(5);
+    let jitter = derive_jitter(second, first, max).unwrap();
+    assert!(jitter >= TimeDelta::zero() && jitter < max);
+    assert_eq!(jitter, derive_jitter(second, first, max).unwrap());
+    // the first draw reduced into range, since a rejection is vanishingly unlikely
+    let mut rng = TwineRng::from_twine(second, first).unwrap();
+    let range = max.num_nanoseconds().unwrap() as u64;
+    assert_eq!(jitter, TimeDelta::nanoseconds((rng.next_u64() % range) as i64));
+
+    assert!(derive_jitter(second, first, TimeDelta::zero()).is_err());
+    assert!(derive_jitter(second, first, TimeDelta::seconds(-1)).is_err());
+  }
+}
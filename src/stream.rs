@@ -0,0 +1,370 @@
+use chrono::{DateTime, Utc};
+use twine_protocol::prelude::*;
+use twine_protocol::twine_lib::Cid;
+
+use crate::resolution::resolution_error;
+use crate::{extract_randomness, RandomnessPayload, RngError};
+#[cfg(feature = "stream")]
+use crate::audit::audited;
+#[cfg(feature = "stream")]
+use crate::{AuditSink, Clock, SystemClock};
+
+/// The last pulse a streaming consumer has processed
+///
+/// Persist this after processing each pulse. After a restart, passing it
+/// to [`next_after`] continues with the following pulse, so no pulse is
+/// skipped or processed twice. CIDs are serialized as strings.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct StreamPosition {
+  #[serde(with = "cid_string")]
+  pub strand_cid: Cid,
+  pub index: u64,
+  #[serde(with = "cid_string")]
+  pub last_cid: Cid,
+  pub last_timestamp: DateTime<Utc>,
+}
+
+mod cid_string {
+  use serde::{Deserialize, Deserializer, Serializer};
+  use twine_protocol::twine_lib::Cid;
+
+  pub fn serialize<S: Serializer>(cid: &Cid, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&cid.to_string())
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Cid, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    Cid::try_from(s).map_err(serde::de::Error::custom)
+  }
+}
+
+impl StreamPosition {
+  pub fn from_twine(twine: &Twine) -> Result<Self, RngError> {
+    let payload = twine.extract_payload::<RandomnessPayload>()?;
+    Ok(Self {
+      strand_cid: twine.strand_cid(),
+      index: twine.index(),
+      last_cid: twine.cid(),
+      last_timestamp: payload.timestamp(),
+    })
+  }
+}
+
+/// Resolve and verify the pulse following a position
+///
+/// Returns `None` if the next pulse hasn't been published yet. The tixel at
+/// the position is re-resolved and must match the recorded CID and timestamp,
+/// so a consumer can't silently resume on a different history.
+pub async fn next_after<R: Resolver>(
+  resolver: &R,
+  position: &StreamPosition,
+) -> Result<Option<(Twine, Vec<u8>)>, RngError> {
+  let Some((next, last)) = resolve_next_after(resolver, position).await? else {
+    return Ok(None);
+  };
+  let rand = extract_randomness(&next, &last)?;
+  Ok(Some((next, rand)))
+}
+
+/// Resolve the pulse following a position along with the pulse at it, unverified
+async fn resolve_next_after<R: Resolver>(
+  resolver: &R,
+  position: &StreamPosition,
+) -> Result<Option<(Twine, Twine)>, RngError> {
+  let latest = resolver.resolve_latest(&position.strand_cid).await.map_err(resolution_error)?.unpack();
+  if latest.index() <= position.index {
+    return Ok(None);
+  }
+  let last = resolver.resolve_index(&position.strand_cid, position.index).await.map_err(resolution_error)?.unpack();
+  let last_timestamp = last.extract_payload::<RandomnessPayload>()?.timestamp();
+  if last.cid() != position.last_cid || last_timestamp != position.last_timestamp {
+    return Err(RngError::PositionMismatch);
+  }
+  let next = if latest.index() == position.index + 1 {
+    latest
+  } else {
+    resolver.resolve_index(&position.strand_cid, position.index + 1).await.map_err(resolution_error)?.unpack()
+  };
+  Ok(Some((next, last)))
+}
+
+/// How long to wait before polling for the pulse after `position`
+///
+/// Until the next pulse is due this is the time remaining. Once it's
+/// overdue, polls are a tenth of the period apart (at least a second).
+#[cfg(feature = "stream")]
+fn poll_delay(position: &StreamPosition, period: chrono::TimeDelta, clock: &dyn Clock) -> chrono::TimeDelta {
+  let due = position.last_timestamp + period;
+  let retry = std::cmp::max(period / 10, chrono::TimeDelta::seconds(1));
+  std::cmp::max(due - clock.now(), retry)
+}
+
+#[cfg(feature = "stream")]
+struct StreamState<R: Resolver, C: Clock> {
+  resolver: R,
+  strand_cid: Cid,
+  position: Option<StreamPosition>,
+  period: Option<chrono::TimeDelta>,
+  clock: C,
+  audit: Option<Box<dyn AuditSink + Send>>,
+}
+
+#[cfg(feature = "stream")]
+impl<R: Resolver, C: Clock> StreamState<R, C> {
+  fn verify(&mut self, current: &Twine, prev: &Twine) -> Result<Vec<u8>, RngError> {
+    let result = extract_randomness(current, prev);
+    match &mut self.audit {
+      Some(sink) => audited(sink.as_mut(), current, result),
+      None => result,
+    }
+  }
+
+  async fn start(&mut self) -> Result<Option<(Twine, Vec<u8>)>, RngError> {
+    let latest = self.resolver.resolve_latest(&self.strand_cid).await.map_err(resolution_error)?.unpack();
+    self.position = Some(StreamPosition::from_twine(&latest)?);
+    // the genesis pulse has no randomness, so wait for the next one
+    let Some(link) = latest.previous() else {
+      return Ok(None);
+    };
+    let prev = self.resolver.resolve(link).await.map_err(resolution_error)?.unpack();
+    let rand = self.verify(&latest, &prev)?;
+    Ok(Some((latest, rand)))
+  }
+
+  async fn period(&mut self) -> Result<chrono::TimeDelta, RngError> {
+    if let Some(period) = self.period {
+      return Ok(period);
+    }
+    let latest = self.resolver.resolve_latest(&self.strand_cid).await.map_err(resolution_error)?.unpack();
+    let period = latest.strand().extract_details::<crate::RngStrandDetails>()?.period;
+    self.period = Some(period);
+    Ok(period)
+  }
+
+  async fn advance(&mut self) -> Result<(Twine, Vec<u8>), RngError> {
+    if self.position.is_none() {
+      if let Some(item) = self.start().await? {
+        return Ok(item);
+      }
+    }
+    loop {
+      let position = self.position.clone().expect("position is set once started");
+      if let Some((next, last)) = resolve_next_after(&self.resolver, &position).await? {
+        let rand = self.verify(&next, &last)?;
+        self.position = Some(StreamPosition::from_twine(&next)?);
+        return Ok((next, rand));
+      }
+      // not published yet, so sleep until it is due, or retry shortly if it's overdue
+      let period = self.period().await?;
+      let wait = poll_delay(&position, period, &self.clock);
+      tokio::time::sleep(wait.to_std().unwrap_or_default()).await;
+    }
+  }
+}
+
+#[cfg(feature = "stream")]
+fn stream_from_state<R: Resolver, C: Clock>(
+  state: StreamState<R, C>,
+) -> impl futures::Stream<Item = Result<(Twine, Vec<u8>), RngError>> {
+  futures::stream::unfold(Some(state), |state| async move {
+    let mut state = state?;
+    match state.advance().await {
+      Ok(item) => Some((Ok(item), Some(state))),
+      // end the stream after reporting an error
+      Err(e) => Some((Err(e), None)),
+    }
+  })
+}
+
+/// A stream of verified randomness, yielding each pulse as it is published
+///
+/// Starts with the latest pulse on the strand. Between pulses, the stream
+/// sleeps until the next pulse is due according to the strand period, and
+/// if a pulse is late it retries at a tenth of the period (at least every
+/// second) rather than yielding a duplicate. If the consumer falls behind,
+/// every intermediate pulse is yielded in order.
+///
+/// The stream ends after yielding the first error.
+#[cfg(feature = "stream")]
+pub fn randomness_stream<R: Resolver>(
+  resolver: R,
+  strand_cid: Cid,
+) -> impl futures::Stream<Item = Result<(Twine, Vec<u8>), RngError>> {
+  randomness_stream_with_clock(resolver, strand_cid, SystemClock)
+}
+
+/// Same as [`randomness_stream`], but timing the waits between pulses with `clock`
+#[cfg(feature = "stream")]
+pub fn randomness_stream_with_clock<R: Resolver, C: Clock>(
+  resolver: R,
+  strand_cid: Cid,
+  clock: C,
+) -> impl futures::Stream<Item = Result<(Twine, Vec<u8>), RngError>> {
+  stream_from_state(StreamState {
+    resolver,
+    strand_cid,
+    position: None,
+    period: None,
+    clock,
+    audit: None,
+  })
+}
+
+/// Same as [`randomness_stream`], but recording every verification to `sink`
+///
+/// See [`crate::extract_randomness_audited`]. If an event can't be
+/// recorded, the stream yields an error and ends.
+#[cfg(feature = "stream")]
+pub fn randomness_stream_audited<R: Resolver, S: AuditSink + Send + 'static>(
+  resolver: R,
+  strand_cid: Cid,
+  sink: S,
+) -> impl futures::Stream<Item = Result<(Twine, Vec<u8>), RngError>> {
+  stream_from_state(StreamState {
+    resolver,
+    strand_cid,
+    position: None,
+    period: None,
+    clock: SystemClock,
+    audit: Some(Box::new(sink)),
+  })
+}
+
+/// Same as [`randomness_stream`], but resuming after a persisted position
+#[cfg(feature = "stream")]
+pub fn randomness_stream_from<R: Resolver>(
+  resolver: R,
+  position: StreamPosition,
+) -> impl futures::Stream<Item = Result<(Twine, Vec<u8>), RngError>> {
+  randomness_stream_from_with_clock(resolver, position, SystemClock)
+}
+
+/// Same as [`randomness_stream_from`], but timing the waits between pulses with `clock`
+#[cfg(feature = "stream")]
+pub fn randomness_stream_from_with_clock<R: Resolver, C: Clock>(
+  resolver: R,
+  position: StreamPosition,
+  clock: C,
+) -> impl futures::Stream<Item = Result<(Twine, Vec<u8>), RngError>> {
+  stream_from_state(StreamState {
+    resolver,
+    strand_cid: position.strand_cid,
+    position: Some(position),
+    period: None,
+    clock,
+    audit: None,
+  })
+}
+
+/// Same as [`randomness_stream_from`], but recording every verification to `sink`
+///
+/// See [`randomness_stream_audited`].
+#[cfg(feature = "stream")]
+pub fn randomness_stream_from_audited<R: Resolver, S: AuditSink + Send + 'static>(
+  resolver: R,
+  position: StreamPosition,
+  sink: S,
+) -> impl futures::Stream<Item = Result<(Twine, Vec<u8>), RngError>> {
+  stream_from_state(StreamState {
+    resolver,
+    strand_cid: position.strand_cid,
+    position: Some(position),
+    period: None,
+    clock: SystemClock,
+    audit: Some(Box::new(sink)),
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use futures::executor::block_on;
+  use crate::test_util::{builder, chain, store};
+  use crate::*;
+  use super::*;
+
+  #[test]
+  fn test_resume_after_restart() {
+    let (builder, strand) = builder();
+    let tixels = chain(&builder, &strand, 4);
+    let store = store(&strand, &tixels);
+
+    // process one pulse, then "crash", keeping only the persisted position
+    let position = StreamPosition::from_twine(&tixels[0]).unwrap();
+    let (next, _) = block_on(next_after(&store, &position)).unwrap().unwrap();
+    assert_eq!(next.cid(), tixels[1].cid());
+    let saved = StreamPosition::from_twine(&next).unwrap();
+
+    // restart from the saved position, as persisted to disk
+    let json = serde_json::to_string(&saved).unwrap();
+    let restored: StreamPosition = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, saved);
+    let (next, rand) = block_on(next_after(&store, &restored)).unwrap().unwrap();
+    assert_eq!(next.cid(), tixels[2].cid());
+    assert_eq!(rand, extract_randomness(&tixels[2], &tixels[1]).unwrap());
+
+    let position = StreamPosition::from_twine(&tixels[3]).unwrap();
+    assert!(block_on(next_after(&store, &position)).unwrap().is_none());
+
+    // a position that doesn't match the strand's history
+    let bogus = StreamPosition { last_cid: tixels[0].cid(), ..StreamPosition::from_twine(&tixels[1]).unwrap() };
+    assert!(matches!(block_on(next_after(&store, &bogus)), Err(RngError::PositionMismatch)));
+  }
+
+  #[cfg(feature = "stream")]
+  #[test]
+  fn test_poll_delay() {
+    use chrono::TimeDelta;
+    let (builder, strand) = builder();
+    let tixels = chain(&builder, &strand, 1);
+    let position = StreamPosition::from_twine(&tixels[0]).unwrap();
+    let period = TimeDelta::seconds(60);
+    let clock = |secs| FixedClock(position.last_timestamp + TimeDelta::seconds(secs));
+
+    assert_eq!(poll_delay(&position, period, &clock(0)), TimeDelta::seconds(60));
+    assert_eq!(poll_delay(&position, period, &clock(45)), TimeDelta::seconds(15));
+    // due or overdue, so retry every tenth of a period
+    assert_eq!(poll_delay(&position, period, &clock(60)), TimeDelta::seconds(6));
+    assert_eq!(poll_delay(&position, period, &clock(600)), TimeDelta::seconds(6));
+    // but no more than once a second
+    assert_eq!(poll_delay(&position, TimeDelta::seconds(5), &clock(600)), TimeDelta::seconds(1));
+  }
+
+  #[cfg(feature = "stream")]
+  #[tokio::test]
+  async fn test_randomness_stream_starts_at_latest() {
+    use futures::StreamExt;
+    let (builder, strand) = builder();
+    let tixels = chain(&builder, &strand, 2);
+    let store = store(&strand, &tixels);
+
+    let stream = randomness_stream(store, strand.cid());
+    futures::pin_mut!(stream);
+    let (twine, rand) = stream.next().await.unwrap().unwrap();
+    assert_eq!(twine.cid(), tixels[1].cid());
+    assert_eq!(rand, extract_randomness(&tixels[1], &tixels[0]).unwrap());
+  }
+
+  #[cfg(feature = "stream")]
+  #[tokio::test]
+  async fn test_randomness_stream_audited() {
+    use futures::StreamExt;
+    use crate::test_util::SharedSink;
+    let (builder, strand) = builder();
+    let tixels = chain(&builder, &strand, 3);
+    let store = store(&strand, &tixels);
+    let sink = SharedSink::default();
+
+    let position = StreamPosition::from_twine(&tixels[0]).unwrap();
+    let stream = randomness_stream_from_audited(store, position, sink.clone());
+    futures::pin_mut!(stream);
+    let (_, first) = stream.next().await.unwrap().unwrap();
+    let (_, second) = stream.next().await.unwrap().unwrap();
+
+    let events = sink.events();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].index, 1);
+    assert_eq!(events[0].output, Some(hex::encode(first)));
+    assert_eq!(events[1].index, 2);
+    assert_eq!(events[1].output, Some(hex::encode(second)));
+  }
+}
@@ -0,0 +1,128 @@
+//! Fixtures shared by the unit tests
+use std::sync::{Arc, Mutex};
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::executor::block_on;
+use twine_protocol::prelude::*;
+use twine_protocol::twine_builder::RingSigner;
+use twine_protocol::twine_lib::multihash_codetable::{Code, MultihashDigest};
+use twine_protocol::twine_lib::store::MemoryStore;
+
+use crate::{subspec_string, AuditEvent, AuditSink, PayloadBuilder, RandomnessPayload, RngStrandDetails};
+
+pub(crate) type Builder = TwineBuilder<2, RingSigner>;
+
+/// A builder and a Sha3_256 strand with a 60 second period
+pub(crate) fn builder() -> (Builder, Strand) {
+  builder_with(Code::Sha3_256, TimeDelta::seconds(60))
+}
+
+/// A builder and a strand with the given hasher and period
+pub(crate) fn builder_with(hasher: Code, period: TimeDelta) -> (Builder, Strand) {
+  let signer = RingSigner::generate_rs256(2048).unwrap();
+  builder_with_signer(signer, hasher, period)
+}
+
+/// A builder using `signer`, and a strand with the given hasher and period
+pub(crate) fn builder_with_signer(signer: RingSigner, hasher: Code, period: TimeDelta) -> (Builder, Strand) {
+  let builder = TwineBuilder::new(signer);
+  let strand = strand_with(&builder, subspec_string(), hasher, period);
+  (builder, strand)
+}
+
+/// A strand with the given subspec, hasher and period
+pub(crate) fn strand_with(builder: &Builder, subspec: String, hasher: Code, period: TimeDelta) -> Strand {
+  builder.build_strand()
+    .subspec(subspec)
+    .hasher(hasher)
+    .details(RngStrandDetails { period })
+    .done()
+    .unwrap()
+}
+
+/// Build `len` tixels on `strand`, committing to `[1; n]`, `[2; n]`, ... in turn
+///
+/// `n` is the digest size of the strand's hasher.
+pub(crate) fn chain(builder: &Builder, strand: &Strand, len: usize) -> Vec<Twine> {
+  let size = strand.hasher().digest(&[]).size() as usize;
+  let values: Vec<Vec<u8>> = (1..=len).map(|i| vec![i as u8; size]).collect();
+  chain_of(builder, strand, &values)
+}
+
+/// Build one tixel on `strand` per value, each committing to its value
+pub(crate) fn chain_of(builder: &Builder, strand: &Strand, values: &[Vec<u8>]) -> Vec<Twine> {
+  let mut pb = PayloadBuilder::new(vec![0u8; values[0].len()], values[0].clone());
+  let mut tixels = vec![builder.build_first(strand.clone())
+    .build_payload_then_done(pb.builder())
+    .unwrap()];
+  for value in &values[1..] {
+    pb = pb.advance(value.clone());
+    let next = builder.build_next(tixels.last().unwrap())
+      .build_payload_then_done(pb.builder())
+      .unwrap();
+    tixels.push(next);
+  }
+  tixels
+}
+
+/// Save a strand and its tixels to a new memory store
+pub(crate) fn store(strand: &Strand, tixels: &[Twine]) -> MemoryStore {
+  let store = MemoryStore::new();
+  block_on(async {
+    store.save(strand.clone()).await.unwrap();
+    for t in tixels {
+      store.save(t.clone()).await.unwrap();
+    }
+  });
+  store
+}
+
+/// Build a genesis pulse at `first_ts` and a valid next pulse `offset` later
+///
+/// `strand` must use Sha3_256.
+/// The payloads are built by hand rather than with a [`PayloadBuilder`], so
+/// neither the timing nor the strand's subspec is checked while building.
+pub(crate) fn pulse_pair_at(
+  builder: &Builder,
+  strand: Strand,
+  first_ts: DateTime<Utc>,
+  offset: TimeDelta,
+) -> (Twine, Twine) {
+  let first = builder.build_first(strand)
+    .payload(RandomnessPayload::try_new(
+      [0u8; 32].to_vec().into(),
+      Code::Sha3_256.digest(&[1u8; 32]),
+      first_ts,
+    ).unwrap())
+    .done()
+    .unwrap();
+  let salt: Vec<u8> = [1u8; 32].iter()
+    .zip(first.cid().hash().digest())
+    .map(|(a, b)| a ^ b)
+    .collect();
+  let second = builder.build_next(&first)
+    .payload(RandomnessPayload::try_new(
+      salt.into(),
+      Code::Sha3_256.digest(&[2u8; 32]),
+      first_ts + offset,
+    ).unwrap())
+    .done()
+    .unwrap();
+  (first, second)
+}
+
+/// An audit sink whose events can be read after handing a clone of it off
+#[derive(Clone, Default)]
+pub(crate) struct SharedSink(Arc<Mutex<Vec<AuditEvent>>>);
+
+impl SharedSink {
+  pub(crate) fn events(&self) -> Vec<AuditEvent> {
+    self.0.lock().unwrap().clone()
+  }
+}
+
+impl AuditSink for SharedSink {
+  fn record(&mut self, event: &AuditEvent) -> std::io::Result<()> {
+    self.0.lock().unwrap().push(event.clone());
+    Ok(())
+  }
+}
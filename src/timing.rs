@@ -1,45 +1,303 @@
-use chrono::{DateTime, TimeDelta, Utc};
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use twine_protocol::prelude::Twine;
+
+use crate::{RandomnessPayload, RngError, RngStrandDetails};
+
+/// A source of the current time
+///
+/// The timing functions that depend on the current time have `_with_clock`
+/// variants accepting a clock, so that pulses can be built (and tested)
+/// against something other than the system clock.
+pub trait Clock {
+  fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system clock, used by the timing functions that don't take a clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+  fn now(&self) -> DateTime<Utc> {
+    Utc::now()
+  }
+}
+
+/// A clock that always reads the same instant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+  fn now(&self) -> DateTime<Utc> {
+    self.0
+  }
+}
 
 pub fn next_truncated_time(period: TimeDelta) -> DateTime<Utc> {
-  let now = Utc::now();
-  use chrono::DurationRound;
-  now.duration_trunc(period).unwrap() + period
+  next_truncated_time_with_clock(period, &SystemClock)
+}
+
+/// Same as [`next_truncated_time`], but reading the time from `clock`
+pub fn next_truncated_time_with_clock(period: TimeDelta, clock: &dyn Clock) -> DateTime<Utc> {
+  clock.now().duration_trunc(period).unwrap() + period
 }
 
 pub fn next_pulse_timestamp(
   prev_time: DateTime<Utc>,
   period: TimeDelta,
 ) -> DateTime<Utc> {
-  let now = Utc::now();
-  if now - prev_time < period {
+  next_pulse_timestamp_with_clock(prev_time, period, &SystemClock)
+}
+
+/// Same as [`next_pulse_timestamp`], but reading the time from `clock`
+pub fn next_pulse_timestamp_with_clock(
+  prev_time: DateTime<Utc>,
+  period: TimeDelta,
+  clock: &dyn Clock,
+) -> DateTime<Utc> {
+  next_pulse_timestamp_with_tolerance_with_clock(prev_time, period, TimeDelta::zero(), clock)
+}
+
+/// Same as [`next_pulse_timestamp`], but a pulse that is late by less than
+/// `tolerance` still follows exactly one period after the previous one,
+/// rather than jumping to the next truncated time
+///
+/// The returned timestamp is always on the grid, so pulses built with it
+/// verify as usual.
+pub fn next_pulse_timestamp_with_tolerance(
+  prev_time: DateTime<Utc>,
+  period: TimeDelta,
+  tolerance: TimeDelta,
+) -> DateTime<Utc> {
+  next_pulse_timestamp_with_tolerance_with_clock(prev_time, period, tolerance, &SystemClock)
+}
+
+/// Same as [`next_pulse_timestamp_with_tolerance`], but reading the time from `clock`
+pub fn next_pulse_timestamp_with_tolerance_with_clock(
+  prev_time: DateTime<Utc>,
+  period: TimeDelta,
+  tolerance: TimeDelta,
+  clock: &dyn Clock,
+) -> DateTime<Utc> {
+  let now = clock.now();
+  if now - prev_time < period + tolerance {
     prev_time + period
   } else {
-    next_truncated_time(period)
+    now.duration_trunc(period).unwrap() + period
   }
 }
 
+/// How long until the pulse after `latest` is due
+///
+/// The result is zero or negative if the next pulse is already due (or overdue),
+/// in which case a new pulse should exist.
+pub fn time_until_next_pulse(latest: &Twine) -> Result<TimeDelta, RngError> {
+  time_until_next_pulse_with_clock(latest, &SystemClock)
+}
+
+/// Same as [`time_until_next_pulse`], but reading the time from `clock`
+pub fn time_until_next_pulse_with_clock(
+  latest: &Twine,
+  clock: &dyn Clock,
+) -> Result<TimeDelta, RngError> {
+  let period = latest.strand().extract_details::<RngStrandDetails>()?.period;
+  let timestamp = latest.extract_payload::<RandomnessPayload>()?.timestamp();
+  // an on-time pulse follows exactly one period later (see `next_pulse_timestamp`)
+  Ok(timestamp + period - clock.now())
+}
+
+/// Whether the pulse after `latest` should have been published by now
+pub fn is_pulse_available(latest: &Twine) -> Result<bool, RngError> {
+  is_pulse_available_with_clock(latest, &SystemClock)
+}
+
+/// Same as [`is_pulse_available`], but reading the time from `clock`
+pub fn is_pulse_available_with_clock(
+  latest: &Twine,
+  clock: &dyn Clock,
+) -> Result<bool, RngError> {
+  Ok(time_until_next_pulse_with_clock(latest, clock)? <= TimeDelta::zero())
+}
+
+/// The round number of a pulse
+///
+/// Rounds count whole periods since the genesis pulse, which is round 0.
+/// Timestamps that aren't exactly on the grid are not rounded; they are
+/// rejected with [`RngError::TimestampOffGrid`]. Timestamps before genesis
+/// are rejected with [`RngError::BeforeGenesis`].
+pub fn round_of(
+  payload_timestamp: DateTime<Utc>,
+  genesis_timestamp: DateTime<Utc>,
+  period: TimeDelta,
+) -> Result<u64, RngError> {
+  let offset = payload_timestamp - genesis_timestamp;
+  if offset < TimeDelta::zero() {
+    return Err(RngError::BeforeGenesis);
+  }
+  let (offset_ms, period_ms) = (offset.num_milliseconds(), period.num_milliseconds());
+  if period_ms <= 0 || TimeDelta::milliseconds(offset_ms) != offset || offset_ms % period_ms != 0 {
+    return Err(RngError::TimestampOffGrid);
+  }
+  Ok((offset_ms / period_ms) as u64)
+}
+
+/// The timestamp of a round
+///
+/// The inverse of [`round_of`]. Returns `None` if the timestamp is not
+/// representable.
+pub fn timestamp_of_round(
+  genesis_timestamp: DateTime<Utc>,
+  period: TimeDelta,
+  round: u64,
+) -> Option<DateTime<Utc>> {
+  let offset = i64::try_from(round).ok()
+    .and_then(|r| period.num_milliseconds().checked_mul(r))
+    .and_then(TimeDelta::try_milliseconds)?;
+  genesis_timestamp.checked_add_signed(offset)
+}
+
+/// Verify that a timestamp is aligned to the granularity of the strand
+///
+/// See [`RngStrandDetails::granularity`]. Misaligned timestamps are
+/// rejected with [`RngError::TimestampOffGrid`].
+pub fn verify_timestamp_against_details(
+  timestamp: DateTime<Utc>,
+  details: &RngStrandDetails,
+) -> Result<(), RngError> {
+  let aligned = timestamp.duration_trunc(details.granularity())
+    .map_err(|_| RngError::TimestampOffGrid)?;
+  if aligned != timestamp {
+    return Err(RngError::TimestampOffGrid);
+  }
+  Ok(())
+}
+
 #[cfg(test)]
 mod test {
-  use chrono::DurationRound;
-
+  use twine_protocol::twine_lib::multihash_codetable::{Code, MultihashDigest};
+  use crate::test_util::builder;
   use super::*;
 
   #[test]
   fn test_next_pulse_time() {
     let period = TimeDelta::seconds(60);
-    let ts = Utc::now().duration_trunc(period).unwrap();
+    let ts = DateTime::parse_from_rfc3339("2025-02-12T21:11:00Z").unwrap().to_utc();
+    let clock = FixedClock(ts + TimeDelta::seconds(20));
 
     let prev_time = ts;
-    let next = next_pulse_timestamp(prev_time, period);
+    let next = next_pulse_timestamp_with_clock(prev_time, period, &clock);
     assert_eq!(next, ts + period);
 
     let prev_time = ts - period;
-    let next = next_pulse_timestamp(prev_time, period);
+    let next = next_pulse_timestamp_with_clock(prev_time, period, &clock);
     assert_eq!(next, ts + period);
 
     let period = TimeDelta::minutes(5);
     let prev_time = ts;
-    let next = next_pulse_timestamp(prev_time, period);
+    let next = next_pulse_timestamp_with_clock(prev_time, period, &clock);
     assert_eq!(next, ts + period);
+
+    assert_eq!(next_truncated_time_with_clock(TimeDelta::seconds(60), &clock), ts + TimeDelta::seconds(60));
+  }
+
+  #[test]
+  fn test_next_pulse_time_late() {
+    let period = TimeDelta::seconds(60);
+    let ts = DateTime::parse_from_rfc3339("2025-02-12T21:11:00Z").unwrap().to_utc();
+    let clock = |secs| FixedClock(ts + TimeDelta::seconds(secs));
+
+    // on time
+    assert_eq!(next_pulse_timestamp_with_clock(ts, period, &clock(10)), ts + period);
+    // late by more than a period
+    assert_eq!(next_pulse_timestamp_with_clock(ts, period, &clock(130)), ts + period * 3);
+  }
+
+  #[test]
+  fn test_next_pulse_time_with_tolerance() {
+    let period = TimeDelta::seconds(60);
+    let tolerance = TimeDelta::seconds(5);
+    let ts = DateTime::parse_from_rfc3339("2025-02-12T21:11:00Z").unwrap().to_utc();
+
+    // zero tolerance matches the default
+    let clock = FixedClock(ts + TimeDelta::seconds(61));
+    assert_eq!(
+      next_pulse_timestamp_with_tolerance_with_clock(ts, period, TimeDelta::zero(), &clock),
+      next_pulse_timestamp_with_clock(ts, period, &clock)
+    );
+    assert_eq!(next_pulse_timestamp_with_clock(ts, period, &clock), ts + period * 2);
+    // just inside
+    let clock = FixedClock(ts + TimeDelta::milliseconds(64_999));
+    assert_eq!(next_pulse_timestamp_with_tolerance_with_clock(ts, period, tolerance, &clock), ts + period);
+    // just outside
+    let clock = FixedClock(ts + TimeDelta::seconds(65));
+    assert_eq!(next_pulse_timestamp_with_tolerance_with_clock(ts, period, tolerance, &clock), ts + period * 2);
+  }
+
+  #[test]
+  fn test_rounds() {
+    let period = TimeDelta::seconds(60);
+    let genesis = DateTime::parse_from_rfc3339("2025-02-12T21:11:00Z").unwrap().to_utc();
+
+    assert_eq!(round_of(genesis, genesis, period).unwrap(), 0);
+    assert_eq!(timestamp_of_round(genesis, period, 0).unwrap(), genesis);
+    assert_eq!(round_of(genesis + period, genesis, period).unwrap(), 1);
+
+    let ts = timestamp_of_round(genesis, period, 10_000).unwrap();
+    assert_eq!(ts, genesis + TimeDelta::days(6) + TimeDelta::hours(22) + TimeDelta::minutes(40));
+    assert_eq!(round_of(ts, genesis, period).unwrap(), 10_000);
+
+    let large = 1_000_000_000;
+    let ts = timestamp_of_round(genesis, period, large).unwrap();
+    assert_eq!(round_of(ts, genesis, period).unwrap(), large);
+    assert!(timestamp_of_round(genesis, period, u64::MAX).is_none());
+    assert!(timestamp_of_round(genesis, period, i64::MAX as u64 / 1000).is_none());
+
+    assert!(matches!(
+      round_of(genesis + TimeDelta::seconds(30), genesis, period),
+      Err(RngError::TimestampOffGrid)
+    ));
+    assert!(matches!(
+      round_of(genesis - period, genesis, period),
+      Err(RngError::BeforeGenesis)
+    ));
+  }
+
+  #[test]
+  fn test_timestamp_granularity() {
+    let ts = DateTime::parse_from_rfc3339("2025-02-12T21:11:00Z").unwrap().to_utc();
+
+    let details = RngStrandDetails { period: TimeDelta::seconds(60) };
+    assert!(verify_timestamp_against_details(ts, &details).is_ok());
+    assert!(verify_timestamp_against_details(ts + TimeDelta::seconds(1), &details).is_ok());
+    for off in [TimeDelta::milliseconds(500), TimeDelta::microseconds(1)] {
+      assert!(matches!(verify_timestamp_against_details(ts + off, &details), Err(RngError::TimestampOffGrid)));
+    }
+  }
+
+  #[test]
+  fn test_time_until_next_pulse() {
+    let (builder, strand) = builder();
+    let ts = DateTime::parse_from_rfc3339("2025-02-12T21:11:00Z").unwrap().to_utc();
+    let payload = RandomnessPayload::try_new(
+      vec![0u8; 32].into(),
+      Code::Sha3_256.digest(&[1u8; 32]),
+      ts,
+    ).unwrap();
+    let latest = builder.build_first(strand)
+      .payload(payload)
+      .done()
+      .unwrap();
+
+    let clock = |secs| FixedClock(ts + TimeDelta::seconds(secs));
+    // just published
+    assert_eq!(time_until_next_pulse_with_clock(&latest, &clock(0)).unwrap(), TimeDelta::seconds(60));
+    assert!(!is_pulse_available_with_clock(&latest, &clock(0)).unwrap());
+    // part way through the period
+    assert_eq!(time_until_next_pulse_with_clock(&latest, &clock(45)).unwrap(), TimeDelta::seconds(15));
+    assert!(!is_pulse_available_with_clock(&latest, &clock(45)).unwrap());
+    // exactly due
+    assert!(is_pulse_available_with_clock(&latest, &clock(60)).unwrap());
+    // overdue
+    assert_eq!(time_until_next_pulse_with_clock(&latest, &clock(90)).unwrap(), TimeDelta::seconds(-30));
+    assert!(is_pulse_available_with_clock(&latest, &clock(90)).unwrap());
   }
 }
@@ -0,0 +1,80 @@
+use twine_protocol::prelude::*;
+
+use crate::{extract_randomness_chain, RandomnessPayload, RngError, RngStrandDetails};
+
+/// Render a self-contained JSON verification page for a run of tixels
+///
+/// The run is verified once, up front. For each round the page shows the salt, the
+/// precommitment it published, and (for every round after the first) the
+/// entropy it revealed along with the steps needed to recompute it:
+///
+/// - `revealed = salt XOR digest(previous_cid)`
+/// - `hash(revealed)` equals the previous round's `commitment`
+/// - `output` is the digest of the round's CID
+///
+/// All byte values are lowercase hex. Commitments are full multihashes.
+pub fn transparency_page(tixels: &[Twine]) -> Result<String, RngError> {
+  let outputs = extract_randomness_chain(tixels)?;
+  let first = tixels.first().ok_or(VerificationError::General(
+    "A transparency page requires at least one tixel".to_string(),
+  ))?;
+  let details = first.strand().extract_details::<RngStrandDetails>()?;
+
+  let rounds = tixels
+    .iter()
+    .enumerate()
+    .map(|(i, tixel)| {
+      let payload = tixel.extract_payload::<RandomnessPayload>()?;
+      let mut round = serde_json::json!({
+        "index": tixel.index(),
+        "cid": tixel.cid().to_string(),
+        "timestamp": payload.timestamp(),
+        "salt": hex::encode(payload.salt()),
+        "commitment": hex::encode(payload.pre().to_bytes()),
+      });
+      if i > 0 {
+        let prev = &tixels[i - 1];
+        let revealed = payload.local_random_value(prev);
+        round["previous_cid"] = prev.cid().to_string().into();
+        round["previous_digest"] = hex::encode(prev.cid().hash().digest()).into();
+        round["previous_commitment"] = hex::encode(prev.extract_payload::<RandomnessPayload>()?.pre().to_bytes()).into();
+        round["revealed"] = hex::encode(revealed).into();
+        round["output"] = hex::encode(&outputs[i - 1]).into();
+      }
+      Ok(round)
+    })
+    .collect::<Result<Vec<_>, RngError>>()?;
+
+  let page = serde_json::json!({
+    "strand": first.strand_cid().to_string(),
+    "period_seconds": details.period.num_seconds(),
+    "recomputation": [
+      "revealed = salt XOR previous_digest",
+      "hash(revealed) == previous_commitment",
+      "output = digest of cid",
+    ],
+    "rounds": rounds,
+  });
+  serde_json::to_string_pretty(&page)
+    .map_err(|e| VerificationError::General(format!("Unable to render transparency page: {}", e)).into())
+}
+
+#[cfg(test)]
+mod test {
+  use crate::test_util::{builder, chain};
+  use crate::*;
+
+  #[test]
+  fn test_transparency_page() {
+    let (builder, strand) = builder();
+    let tixels = chain(&builder, &strand, 2);
+
+    let page = transparency_page(&tixels).unwrap();
+    let page: serde_json::Value = serde_json::from_str(&page).unwrap();
+    let rounds = page["rounds"].as_array().unwrap();
+    assert_eq!(rounds.len(), 2);
+    assert!(rounds[0].get("revealed").is_none());
+    assert_eq!(rounds[1]["revealed"], "01".repeat(32));
+    assert_eq!(rounds[1]["cid"], tixels[1].cid().to_string());
+  }
+}
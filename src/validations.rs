@@ -1,11 +1,37 @@
-use twine_protocol::{prelude::BuildError, twine_lib::{crypto::SignatureAlgorithm, specification::Subspec}};
-
-pub fn validate_signing_algorithm(alg: SignatureAlgorithm) -> Result<(), BuildError> {
-  match alg {
-    SignatureAlgorithm::Sha256Rsa(_) => Ok(()),
-    SignatureAlgorithm::Sha384Rsa(_) => Ok(()),
-    SignatureAlgorithm::Sha512Rsa(_) => Ok(()),
-    _ => Err(BuildError::PayloadConstruction("Signature algorithm must be provably deterministic".to_string())),
+use twine_protocol::{prelude::BuildError, twine_lib::{crypto::SignatureAlgorithm, semver::{Version, VersionReq}, specification::Subspec}};
+
+use crate::RngError;
+
+/// The versions of the twine-rng specification this crate supports
+pub(crate) const SUPPORTED_VERSIONS: &str = "1.0.*";
+
+/// Whether a signature algorithm is allowed for rng strands
+///
+/// Tixel CIDs are the source of randomness, so the signature must be
+/// deterministic or a producer could grind signatures for a favourable
+/// output. Currently the RSA PKCS#1 v1.5 variants (SHA-256, SHA-384 and
+/// SHA-512) and Ed25519 are allowed. Signatures are verified by
+/// `twine_protocol` when tixels are decoded, so any crypto backend used
+/// there must support these.
+///
+/// Ed25519 signatures are deterministic as specified in RFC 8032. Some
+/// signers deliberately add randomness to Ed25519 signatures ("hedged"
+/// signing); such signers must not be used to produce rng strands.
+pub fn is_deterministic_signing_algorithm(alg: &SignatureAlgorithm) -> bool {
+  matches!(
+    alg,
+    SignatureAlgorithm::Sha256Rsa(_)
+      | SignatureAlgorithm::Sha384Rsa(_)
+      | SignatureAlgorithm::Sha512Rsa(_)
+      | SignatureAlgorithm::Ed25519
+  )
+}
+
+pub fn validate_signing_algorithm(alg: SignatureAlgorithm) -> Result<(), RngError> {
+  if is_deterministic_signing_algorithm(&alg) {
+    Ok(())
+  } else {
+    Err(RngError::UnsupportedSigningAlgorithm)
   }
 }
 
@@ -17,4 +43,66 @@ pub fn validate_subspec(subspec: &Subspec) -> Result<(), BuildError> {
     )));
   }
   Ok(())
-}
\ No newline at end of file
+}
+
+/// Strictly parse a subspec string of the form `twine-rng/<version>`
+///
+/// The version must be a full semver version (e.g. `1.0.0`, not `1.0`)
+/// within the range of versions this crate supports. Returns the version.
+pub fn validate_subspec_string(subspec: &str) -> Result<Version, RngError> {
+  let (prefix, version) = subspec.split_once('/').ok_or(RngError::InvalidSubspec(
+    "Subspec must be of the form prefix/version".to_string(),
+  ))?;
+  if prefix != crate::SPEC_PREFIX {
+    return Err(RngError::InvalidSubspec(format!(
+      "Subspec prefix must be {}",
+      crate::SPEC_PREFIX
+    )));
+  }
+  if version.is_empty() {
+    return Err(RngError::InvalidSubspec("Subspec is missing a version".to_string()));
+  }
+  let version = Version::parse(version)
+    .map_err(|e| RngError::InvalidSubspec(format!("Subspec version is not valid semver: {}", e)))?;
+  if !VersionReq::parse(SUPPORTED_VERSIONS).unwrap().matches(&version) {
+    return Err(RngError::InvalidSubspec(format!(
+      "Subspec version {} is not supported",
+      version
+    )));
+  }
+  Ok(version)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_subspec_string() {
+    assert_eq!(validate_subspec_string("twine-rng/1.0.0").unwrap(), Version::new(1, 0, 0));
+    assert!(validate_subspec_string("twine-rng/1.0.3").is_ok());
+
+    for bad in [
+      "twine-rng",
+      "twine-rng/",
+      "twine-rng/1.0",
+      "twine-rng/one",
+      "twine-rnd/1.0.0",
+      "twine-rng/2.0.0",
+    ] {
+      assert!(matches!(validate_subspec_string(bad), Err(RngError::InvalidSubspec(_))), "Should reject {}", bad);
+    }
+  }
+
+  #[test]
+  fn test_signing_algorithm() {
+    for alg in [SignatureAlgorithm::Sha256Rsa(2048), SignatureAlgorithm::Sha512Rsa(4096), SignatureAlgorithm::Ed25519] {
+      assert!(validate_signing_algorithm(alg).is_ok());
+    }
+    // ECDSA signatures use a random nonce, so they could be ground
+    for alg in [SignatureAlgorithm::EcdsaP256, SignatureAlgorithm::EcdsaP384] {
+      assert!(!is_deterministic_signing_algorithm(&alg));
+      assert!(matches!(validate_signing_algorithm(alg), Err(RngError::UnsupportedSigningAlgorithm)));
+    }
+  }
+}
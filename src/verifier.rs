@@ -0,0 +1,107 @@
+use twine_protocol::prelude::*;
+
+use crate::{extract_randomness_chain, validations, RngError};
+
+/// Verifies tixel signatures against their strand
+///
+/// [`Twine::try_new`] verifies signatures with the crypto backend built into
+/// `twine_protocol`. Implement this to verify with another backend (e.g.
+/// aws-lc or an HSM) and pass it to [`extract_randomness_chain_verified_by`].
+/// An implementation must support every algorithm accepted by
+/// [`crate::is_deterministic_signing_algorithm`]: Ed25519 and RSA PKCS#1 v1.5
+/// with SHA-256, SHA-384 or SHA-512.
+pub trait TixelVerifier {
+  /// Verify the signature of `tixel` against `strand`, returning the pair as a twine
+  fn verify(&self, strand: &Strand, tixel: &Tixel) -> Result<Twine, RngError>;
+}
+
+/// The [`TixelVerifier`] using the crypto backend built into `twine_protocol`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultTixelVerifier;
+
+impl TixelVerifier for DefaultTixelVerifier {
+  fn verify(&self, strand: &Strand, tixel: &Tixel) -> Result<Twine, RngError> {
+    Ok(Twine::try_new(strand.clone(), tixel.clone())?)
+  }
+}
+
+/// Verify the tixel at position `index` of a chain, reporting failure as a broken link
+pub(crate) fn verify_link<V: TixelVerifier + ?Sized>(
+  verifier: &V,
+  strand: &Strand,
+  index: usize,
+  tixel: &Tixel,
+) -> Result<Twine, RngError> {
+  verifier.verify(strand, tixel).map_err(|e| RngError::BrokenLink {
+    index,
+    cid: tixel.cid(),
+    source: Box::new(e),
+  })
+}
+
+/// Verify a chain of tixels with `verifier`, then extract its randomness
+///
+/// The strand must be signed with a deterministic algorithm, or
+/// [`RngError::UnsupportedSigningAlgorithm`] is returned before any tixel is
+/// verified. Each tixel is then verified against the strand with `verifier`,
+/// and the chain is checked as in [`extract_randomness_chain`]. If a tixel
+/// fails to verify, [`RngError::BrokenLink`] reports its index.
+pub fn extract_randomness_chain_verified_by<V: TixelVerifier + ?Sized>(
+  strand: &Strand,
+  tixels: &[Tixel],
+  verifier: &V,
+) -> Result<Vec<Vec<u8>>, RngError> {
+  validations::validate_signing_algorithm(strand.key().alg)?;
+  let twines = tixels
+    .iter()
+    .enumerate()
+    .map(|(i, tixel)| verify_link(verifier, strand, i, tixel))
+    .collect::<Result<Vec<_>, _>>()?;
+  extract_randomness_chain(&twines)
+}
+
+#[cfg(test)]
+mod test {
+  use chrono::TimeDelta;
+  use twine_protocol::twine_builder::RingSigner;
+  use twine_protocol::twine_lib::multihash_codetable::Code;
+  use crate::test_util::{builder, builder_with_signer, chain};
+  use crate::*;
+
+  struct RejectIndex(u64);
+
+  impl TixelVerifier for RejectIndex {
+    fn verify(&self, strand: &Strand, tixel: &Tixel) -> Result<Twine, RngError> {
+      if tixel.index() == self.0 {
+        return Err(VerificationError::General("rejected by backend".to_string()).into());
+      }
+      DefaultTixelVerifier.verify(strand, tixel)
+    }
+  }
+
+  #[test]
+  fn test_custom_verifier() {
+    let (builder, strand) = builder();
+    let twines = chain(&builder, &strand, 4);
+    let tixels: Vec<Tixel> = twines.iter().map(|t| t.tixel().clone()).collect();
+
+    assert_eq!(
+      extract_randomness_chain_verified_by(&strand, &tixels, &DefaultTixelVerifier).unwrap(),
+      extract_randomness_chain(&twines).unwrap()
+    );
+    match extract_randomness_chain_verified_by(&strand, &tixels, &RejectIndex(2)).unwrap_err() {
+      RngError::BrokenLink { index, cid, .. } => {
+        assert_eq!(index, 2);
+        assert_eq!(cid, tixels[2].cid());
+      }
+      e => panic!("Unexpected error: {}", e),
+    }
+
+    let signer = RingSigner::generate_p256().unwrap();
+    let (_, strand) = builder_with_signer(signer, Code::Sha3_256, TimeDelta::seconds(60));
+    assert!(matches!(
+      extract_randomness_chain_verified_by(&strand, &[], &DefaultTixelVerifier),
+      Err(RngError::UnsupportedSigningAlgorithm)
+    ));
+  }
+}
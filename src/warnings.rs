@@ -0,0 +1,117 @@
+use std::fmt::Display;
+use twine_protocol::prelude::*;
+
+use crate::{extract_randomness, hash_strength_bits, RandomnessPayload, RngError};
+
+/// The smallest precommitment hash size, in bits, that doesn't raise a
+/// [`Warning::WeakCommitment`]
+pub const RECOMMENDED_HASH_BITS: usize = 256;
+
+/// A suspicious property of a pulse that doesn't make it invalid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+  /// The pulse's precommitment uses a hash with fewer than
+  /// [`RECOMMENDED_HASH_BITS`] bits. The commitment is still binding, but
+  /// offers less margin against a producer searching for collisions.
+  WeakCommitment { bits: usize },
+  /// The pulse publishes the same precommitment as the previous pulse. This
+  /// means the producer reused a random value, so the next reveal is already
+  /// known to anyone who saw this one.
+  RepeatedCommitment,
+  /// The value revealed by the pulse is a single repeated byte (such as all
+  /// zeros). This usually indicates a misconfigured or failing entropy source.
+  DegenerateDigest,
+}
+
+impl Display for Warning {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Warning::WeakCommitment { bits } => write!(
+        f,
+        "Precommitment hash provides {} bits, fewer than the recommended {}",
+        bits, RECOMMENDED_HASH_BITS
+      ),
+      Warning::RepeatedCommitment => write!(f, "Precommitment repeats the previous pulse's precommitment"),
+      Warning::DegenerateDigest => write!(f, "Revealed value is a single repeated byte"),
+    }
+  }
+}
+
+fn is_degenerate(bytes: &[u8]) -> bool {
+  bytes.windows(2).all(|w| w[0] == w[1])
+}
+
+/// Check a pulse for suspicious properties without rejecting it
+///
+/// Returns nothing if either payload can't be read; that case is already
+/// reported by [`extract_randomness`].
+pub fn pulse_warnings(current: &Twine, prev: &Twine) -> Vec<Warning> {
+  let (Ok(payload), Ok(prev_payload)) = (
+    current.extract_payload::<RandomnessPayload>(),
+    prev.extract_payload::<RandomnessPayload>(),
+  ) else {
+    return vec![];
+  };
+  let mut warnings = vec![];
+  if let Ok(bits) = hash_strength_bits(payload.pre().code()) {
+    if bits < RECOMMENDED_HASH_BITS {
+      warnings.push(Warning::WeakCommitment { bits });
+    }
+  }
+  if payload.pre() == prev_payload.pre() {
+    warnings.push(Warning::RepeatedCommitment);
+  }
+  if is_degenerate(&payload.local_random_value(prev)) {
+    warnings.push(Warning::DegenerateDigest);
+  }
+  warnings
+}
+
+/// Verify a pulse, also reporting any warnings
+///
+/// The result is the same as [`extract_randomness`]. The warnings are
+/// collected whether or not verification passes, so a consumer can log
+/// suspicious pulses without rejecting them.
+pub fn verify_with_warnings(current: &Twine, prev: &Twine) -> (Result<Vec<u8>, RngError>, Vec<Warning>) {
+  (extract_randomness(current, prev), pulse_warnings(current, prev))
+}
+
+#[cfg(test)]
+mod test {
+  use crate::test_util::{builder, chain_of};
+  use crate::*;
+
+  fn chain(values: &[[u8; 32]]) -> Vec<Twine> {
+    let (builder, strand) = builder();
+    let values: Vec<Vec<u8>> = values.iter().map(|v| v.to_vec()).collect();
+    chain_of(&builder, &strand, &values)
+  }
+
+  #[test]
+  fn test_no_warnings() {
+    let mut a = [0u8; 32];
+    let mut b = [0u8; 32];
+    let mut c = [0u8; 32];
+    for i in 0..32u8 {
+      a[i as usize] = i;
+      b[i as usize] = i + 32;
+      c[i as usize] = i + 64;
+    }
+    let tixels = chain(&[a, b, c]);
+    let (result, warnings) = verify_with_warnings(&tixels[2], &tixels[1]);
+    assert!(result.is_ok());
+    assert_eq!(warnings, vec![]);
+  }
+
+  #[test]
+  fn test_warnings_do_not_fail() {
+    let tixels = chain(&[[1u8; 32], [1u8; 32], [2u8; 32]]);
+    let (result, warnings) = verify_with_warnings(&tixels[1], &tixels[0]);
+    assert!(result.is_ok());
+    assert_eq!(warnings, vec![Warning::RepeatedCommitment, Warning::DegenerateDigest]);
+
+    let (result, warnings) = verify_with_warnings(&tixels[2], &tixels[1]);
+    assert!(result.is_ok());
+    assert_eq!(warnings, vec![Warning::DegenerateDigest]);
+  }
+}